@@ -6,19 +6,19 @@ pub struct AnimationReel {
     pub currTextureIndex: u32,
 
     timeInterval: f64,
-    perFrameTimeIncrement: f64,
+    perTickTimeIncrement: f64,
     timer: f64,
 
     loopbackClipIndex: Option<u32>
 }
 
 impl AnimationReel {
-    pub fn New(textures: Vec<TextureHandle>, timeInterval: f64, perFrameTimeIncrement: f64, loopbackClipIndex: Option<u32>) -> Self {
+    pub fn New(textures: Vec<TextureHandle>, timeInterval: f64, perTickTimeIncrement: f64, loopbackClipIndex: Option<u32>) -> Self {
         Self {
             textures,
             currTextureIndex: 0,
             timeInterval,
-            perFrameTimeIncrement,
+            perTickTimeIncrement,
             timer: 0.0,
             loopbackClipIndex
         }
@@ -54,7 +54,7 @@ impl AnimationReel {
             }
         // If timer is not up
         } else {
-            self.timer += self.perFrameTimeIncrement;
+            self.timer += self.perTickTimeIncrement;
             None
         }
     }
@@ -103,6 +103,21 @@ impl AnimationMagazine {
         }
     }
 
+    // Starts a clip from its first frame, whatever frame it was left on
+    pub fn PlayClip(&mut self, clipIndex: u32) {
+        self.currClipIndex = clipIndex;
+        if let AnimationClip::REEL(reel) = &mut self.clips[clipIndex as usize] {
+            reel.Reset();
+        }
+    }
+
+    pub fn GetCurrFrameIndex(&self) -> u32 {
+        match &self.clips[self.currClipIndex as usize] {
+            AnimationClip::STATIC(_) => 0,
+            AnimationClip::REEL(reel) => reel.currTextureIndex
+        }
+    }
+
     pub fn SwitchClipIndexWithTimeCopy(&mut self, clipIndexToSwitchTo: usize) {
         let backupTextureIndex;
         let backupTimer;
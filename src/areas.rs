@@ -0,0 +1,126 @@
+use crate::tiles::Tile;
+use crate::utils::vec2d::iPoint2;
+
+/*
+=========================================================
+    Map areas
+
+    Floor space split into areas the way the original game does it: every
+    run of open tiles (empty or holding an object) that's connected without
+    going through a wall or door is one area, numbered by a floodfill when
+    the map loads. Doors sit between areas, and while a door isn't fully
+    shut the areas on its sides are joined, e.g. for a gunshot to be heard.
+    Door status is read at the time of asking, so opening or closing a door
+    changes what's connected straight away.
+=========================================================
+*/
+
+const NEIGHBOUR_OFFSETS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+pub struct Areas {
+    // Indexed [x][y], like the map's tiles ; None for walls and doors
+    tileAreas: Vec<Vec<Option<usize>>>,
+
+    // Each door's tile and the areas it sits between
+    doors: Vec<(iPoint2, Vec<usize>)>,
+
+    pub numAreas: usize
+}
+
+impl Areas {
+    pub fn New(tiles: &[Vec<Tile>]) -> Self {
+        let width = tiles.len();
+        let height = tiles.first().map_or(0, |column| column.len());
+        let GetTile = |tileCoord: iPoint2| {
+            let inGrid = tileCoord.x() >= 0 && (tileCoord.x() as usize) < width && tileCoord.y() >= 0 && (tileCoord.y() as usize) < height;
+            if inGrid { Some(&tiles[tileCoord.x() as usize][tileCoord.y() as usize]) } else { None }
+        };
+        let IsFloor = |tileCoord: iPoint2| matches!(GetTile(tileCoord), Some(Tile::EMPTY(_)) | Some(Tile::OBJECT(_)));
+
+        let mut tileAreas: Vec<Vec<Option<usize>>> = vec![vec![None; height]; width];
+        let mut numAreas = 0;
+        for x in 0..width {
+            for y in 0..height {
+                let start = iPoint2::New(x as i32, y as i32);
+                if tileAreas[x][y].is_some() || !IsFloor(start) {
+                    continue;
+                }
+
+                tileAreas[x][y] = Some(numAreas);
+                let mut toVisit = vec![start];
+                while let Some(tile) = toVisit.pop() {
+                    for (dx, dy) in NEIGHBOUR_OFFSETS {
+                        let neighbour = iPoint2::New(tile.x() + dx, tile.y() + dy);
+                        if IsFloor(neighbour) && tileAreas[neighbour.x() as usize][neighbour.y() as usize].is_none() {
+                            tileAreas[neighbour.x() as usize][neighbour.y() as usize] = Some(numAreas);
+                            toVisit.push(neighbour);
+                        }
+                    }
+                }
+                numAreas += 1;
+            }
+        }
+
+        let mut areas = Self { tileAreas, doors: Vec::new(), numAreas };
+        for (x, column) in tiles.iter().enumerate() {
+            for (y, tile) in column.iter().enumerate() {
+                if let Tile::DOOR(_) = tile {
+                    let doorTile = iPoint2::New(x as i32, y as i32);
+                    let sides = areas.NeighbourAreas(doorTile);
+                    areas.doors.push((doorTile, sides));
+                }
+            }
+        }
+        areas
+    }
+
+    // The tile's own area ; a door tile is in the areas on its sides, since whoever stands in a doorway is in both
+    pub fn GetAreas(&self, tileCoord: iPoint2) -> Vec<usize> {
+        match self.GetArea(tileCoord) {
+            Some(area) => vec![area],
+            None => match self.doors.iter().find(|(doorTile, _)| *doorTile == tileCoord) {
+                Some((_, sides)) => sides.clone(),
+                None => Vec::new()
+            }
+        }
+    }
+
+    // Which areas connect to the tile's, going through doors IsDoorOpen lets through ; indexed by area
+    pub fn ConnectedAreas(&self, tileCoord: iPoint2, IsDoorOpen: impl Fn(iPoint2) -> bool) -> Vec<bool> {
+        let mut connected = vec![false; self.numAreas];
+        for area in self.GetAreas(tileCoord) {
+            connected[area] = true;
+        }
+
+        let openDoors: Vec<&Vec<usize>> = self.doors.iter().filter(|(doorTile, _)| IsDoorOpen(*doorTile)).map(|(_, sides)| sides).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for sides in &openDoors {
+                if sides.iter().any(|&area| connected[area]) && !sides.iter().all(|&area| connected[area]) {
+                    for &area in sides.iter() {
+                        connected[area] = true;
+                    }
+                    changed = true;
+                }
+            }
+        }
+        connected
+    }
+
+    fn GetArea(&self, tileCoord: iPoint2) -> Option<usize> {
+        self.tileAreas.get(tileCoord.x() as usize)?.get(tileCoord.y() as usize).copied().flatten()
+    }
+
+    fn NeighbourAreas(&self, tileCoord: iPoint2) -> Vec<usize> {
+        let mut neighbourAreas: Vec<usize> = Vec::new();
+        for (dx, dy) in NEIGHBOUR_OFFSETS {
+            if let Some(area) = self.GetArea(iPoint2::New(tileCoord.x() + dx, tileCoord.y() + dy)) {
+                if !neighbourAreas.contains(&area) {
+                    neighbourAreas.push(area);
+                }
+            }
+        }
+        neighbourAreas
+    }
+}
@@ -0,0 +1,132 @@
+/*
+=========================================================
+    Map area tests
+
+    Three rooms in a row joined by two doors, the player in the west room
+    and a standing guard facing away from them in each of the others.
+=========================================================
+*/
+
+use crate::enemy::EnemyState;
+use crate::game_state::{GameState, TickInputs};
+use crate::tiles::{DoorStatus, Tile};
+use crate::utils::scratch::WriteScratchFile;
+use crate::utils::vec2d::{iPoint2, Vec2};
+
+const SCRATCH_DIR: &str = "target/areas_tests";
+
+const THREE_ROOMS: &str = "\
+[map]
+name = Three rooms
+width = 11
+height = 5
+
+[walls]
+15,15,15,15,15,15,15,15,15,15,15
+15,,,15,,,,15,,,15
+15,,,D,,,,D,,,15
+15,,,15,,,,15,,,15
+15,15,15,15,15,15,15,15,15,15,15
+
+[actors]
+,,,,,,,,,,
+,,,,,,,,,,
+,P-W,,,,GU-S,,,,GU-S,
+,,,,,,,,,,
+,,,,,,,,,,
+";
+
+const WEST_DOOR: (i32, i32) = (3, 2);
+
+fn LoadThreeRooms(fileName: &str) -> GameState {
+    let path = WriteScratchFile(SCRATCH_DIR, fileName, THREE_ROOMS);
+    GameState::New(&path, 0, false).unwrap()
+}
+
+fn SetDoorStatus(gameState: &mut GameState, doorTile: (i32, i32), status: DoorStatus) {
+    if let Tile::DOOR(door) = gameState.map.GetMutTile(iPoint2::New(doorTile.0, doorTile.1)) {
+        door.status = status;
+    }
+}
+
+// Guards get a random facing when a map loads ; both are turned to face east, away from the player
+fn GuardIn(gameState: &mut GameState, column: i32) -> usize {
+    let index = gameState.enemies.iter().position(|e| e.tile.x() == column).unwrap();
+    gameState.enemies[index].viewDir = Vec2::New(1.0, 0.0);
+    index
+}
+
+#[test]
+fn floodfill_numbers_each_room_and_doors_sit_between_them() {
+    let gameState = LoadThreeRooms("numbering.wmap");
+    let map = &gameState.map;
+    let AreaOf = |x: i32, y: i32| map.GetAreas(iPoint2::New(x, y));
+
+    assert_eq!(map.AreasInEarshot(iPoint2::New(1, 1)).len(), 3);
+    assert_eq!(AreaOf(1, 1), AreaOf(2, 3));
+    assert_eq!(AreaOf(4, 1), AreaOf(6, 3));
+    assert_ne!(AreaOf(1, 2), AreaOf(5, 2));
+    assert_ne!(AreaOf(5, 2), AreaOf(9, 2));
+
+    let mut westDoorAreas = AreaOf(WEST_DOOR.0, WEST_DOOR.1);
+    westDoorAreas.sort();
+    let mut expected = [AreaOf(1, 2), AreaOf(5, 2)].concat();
+    expected.sort();
+    assert_eq!(westDoorAreas, expected);
+    assert!(AreaOf(3, 1).is_empty(), "walls aren't in any area");
+}
+
+#[test]
+fn connectivity_follows_door_status() {
+    let mut gameState = LoadThreeRooms("connectivity.wmap");
+    let playerTile = iPoint2::New(1, 2);
+    let Heard = |gameState: &GameState| {
+        let heard = gameState.map.AreasInEarshot(playerTile);
+        [1, 5, 9].map(|x| gameState.map.GetAreas(iPoint2::New(x, 2)).iter().any(|&area| heard[area]))
+    };
+
+    assert_eq!(Heard(&gameState), [true, false, false]);
+
+    SetDoorStatus(&mut gameState, WEST_DOOR, DoorStatus::OPENING);
+    assert_eq!(Heard(&gameState), [true, true, false]);
+
+    SetDoorStatus(&mut gameState, (7, 2), DoorStatus::OPEN);
+    assert_eq!(Heard(&gameState), [true, true, true]);
+
+    SetDoorStatus(&mut gameState, WEST_DOOR, DoorStatus::CLOSED);
+    assert_eq!(Heard(&gameState), [true, false, false]);
+}
+
+#[test]
+fn gunshot_alerts_only_enemies_behind_open_doors() {
+    let mut gameState = LoadThreeRooms("gunshot.wmap");
+    let middleGuard = GuardIn(&mut gameState, 5);
+    let eastGuard = GuardIn(&mut gameState, 9);
+    SetDoorStatus(&mut gameState, WEST_DOOR, DoorStatus::OPENING);
+
+    gameState.Step(&TickInputs { fireWeapon: true, ..TickInputs::default() });
+    for _ in 0..60 {
+        gameState.Step(&TickInputs::default());
+    }
+
+    // The middle guard can't see the player, facing away, but heads for where the shot came from
+    assert_ne!(gameState.enemies[middleGuard].currState, EnemyState::STAND);
+    assert!(gameState.enemies[middleGuard].location.x() < 5.0, "guard should have moved toward the noise, ended at {}", gameState.enemies[middleGuard].location.x());
+
+    assert_eq!(gameState.enemies[eastGuard].currState, EnemyState::STAND);
+    assert_eq!(gameState.enemies[eastGuard].tile, iPoint2::New(9, 2));
+}
+
+#[test]
+fn holding_fire_is_heard_once_per_shot() {
+    let mut gameState = LoadThreeRooms("held_fire.wmap");
+    let middleGuard = GuardIn(&mut gameState, 5);
+    SetDoorStatus(&mut gameState, WEST_DOOR, DoorStatus::OPENING);
+    let fire = TickInputs { fireWeapon: true, ..TickInputs::default() };
+
+    // Noises are left for the enemy to pick up on its next update
+    gameState.Step(&fire);
+    assert!(gameState.enemies[middleGuard].inputsBuffer.noise.is_some());
+    gameState.Step(&fire);
+    assert!(gameState.enemies[middleGuard].inputsBuffer.noise.is_none(), "the weapon is still going through the first shot");
+}
@@ -0,0 +1,220 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use csv::{ReaderBuilder, Writer};
+use crate::enemy::EnemyState;
+use crate::game_state::{GameState, TickInputs, moveCommand_t};
+use crate::utils::csv::ParseCSVHeader;
+
+/*
+=========================================================
+    Demo file format
+
+    A demo is a CSV file with one row per simulation tick, holding the inputs
+    that tick was stepped with. `# key = value` lines at the top (same syntax
+    as CSV map headers) record what is needed to start the run identically:
+
+        # map = assets/map.wmap
+        # seed = 1234
+        # vertical_look = true
+        move,mouse_xrel,mouse_yrel,door,fire
+        NORTH,0,0,0,0
+        NORTH_WEST,-12,3,0,1
+        ...
+=========================================================
+*/
+
+pub struct DemoHeader {
+    pub mapPath: String,
+    pub seed: u64,
+    pub verticalLook: bool
+}
+
+fn ParseMoveCommand(name: &str) -> Option<moveCommand_t> {
+    match name {
+        "NORTH" => Some(moveCommand_t::NORTH),
+        "SOUTH" => Some(moveCommand_t::SOUTH),
+        "EAST" => Some(moveCommand_t::EAST),
+        "WEST" => Some(moveCommand_t::WEST),
+        "NORTH_EAST" => Some(moveCommand_t::NORTH_EAST),
+        "NORTH_WEST" => Some(moveCommand_t::NORTH_WEST),
+        "NONE" => Some(moveCommand_t::NONE),
+        _ => None
+    }
+}
+
+/*
+=========================================================
+    Recording
+=========================================================
+*/
+
+pub struct DemoRecorder {
+    pub path: String,
+    writer: Writer<File>
+}
+
+impl DemoRecorder {
+    pub fn New(path: &str, header: &DemoHeader) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::create(path)?;
+        writeln!(file, "# map = {}", header.mapPath)?;
+        writeln!(file, "# seed = {}", header.seed)?;
+        writeln!(file, "# vertical_look = {}", header.verticalLook)?;
+
+        let mut writer = Writer::from_writer(file);
+        writer.write_record(["move", "mouse_xrel", "mouse_yrel", "door", "fire"])?;
+
+        Ok(Self {
+            path: path.to_string(),
+            writer
+        })
+    }
+
+    // Call with the inputs each tick is stepped with, in order
+    pub fn Record(&mut self, tick: &TickInputs) -> Result<(), csv::Error> {
+        self.writer.write_record(&[
+            format!("{:?}", tick.moveCommand),
+            tick.mouseXrel.to_string(),
+            tick.mouseYrel.to_string(),
+            (tick.openDoor as u8).to_string(),
+            (tick.fireWeapon as u8).to_string()
+        ])
+    }
+}
+
+impl Drop for DemoRecorder {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+/*
+=========================================================
+    Playback
+=========================================================
+*/
+
+pub struct Demo {
+    pub header: DemoHeader,
+    pub ticks: Vec<TickInputs>
+}
+
+impl Demo {
+    pub fn Load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let headerFields = ParseCSVHeader(path)?;
+        let GetField = |key: &str| headerFields.get(key).ok_or_else(|| format!("{}: missing `# {} = ...` header line", path, key));
+        let header = DemoHeader {
+            mapPath: GetField("map")?.clone(),
+            seed: GetField("seed")?.parse::<u64>().map_err(|e| format!("{}: bad seed: {}", path, e))?,
+            verticalLook: GetField("vertical_look")?.parse::<bool>().map_err(|e| format!("{}: bad vertical_look: {}", path, e))?
+        };
+
+        let mut reader = ReaderBuilder::new()
+            .has_headers(true)
+            .comment(Some(b'#'))
+            .from_path(path)?;
+
+        let mut ticks: Vec<TickInputs> = Vec::new();
+        for (i, result) in reader.records().enumerate() {
+            let record = result?;
+            let BadTick = || format!("{}: malformed tick {} ({:?})", path, i, record);
+            if record.len() != 5 {
+                return Err(BadTick().into());
+            }
+            ticks.push(TickInputs {
+                moveCommand: ParseMoveCommand(&record[0]).ok_or_else(BadTick)?,
+                mouseXrel: record[1].parse::<i32>().map_err(|_| BadTick())?,
+                mouseYrel: record[2].parse::<i32>().map_err(|_| BadTick())?,
+                openDoor: &record[3] == "1",
+                fireWeapon: &record[4] == "1"
+            });
+        }
+
+        Ok(Self {
+            header,
+            ticks
+        })
+    }
+}
+
+pub struct DemoPlayback {
+    pub demo: Demo,
+    nextTick: usize
+}
+
+impl DemoPlayback {
+    pub fn New(demo: Demo) -> Self {
+        Self {
+            demo,
+            nextTick: 0
+        }
+    }
+
+    // The next recorded tick's inputs ; None once the demo has run out
+    pub fn Next(&mut self) -> Option<TickInputs> {
+        let tick = self.demo.ticks.get(self.nextTick).copied();
+        if tick.is_some() {
+            self.nextTick += 1;
+        }
+        tick
+    }
+}
+
+/*
+=========================================================
+    Simulation report
+=========================================================
+*/
+
+#[derive(PartialEq, Debug)]
+pub struct EnemyReport {
+    pub enemyCode: String,
+    pub location: (f64, f64),
+    pub health: i32,
+    pub state: EnemyState
+}
+
+// Where everything ended up after a run ; printed by headless demo playback and compared by tests
+#[derive(PartialEq, Debug)]
+pub struct SimulationReport {
+    pub ticks: usize,
+    pub playerLocation: (f64, f64),
+    pub playerViewDir: (f64, f64),
+    pub playerPitch: f64,
+    pub playerHealth: i32,
+    pub playerScore: u32,
+    pub enemies: Vec<EnemyReport>
+}
+
+impl SimulationReport {
+    pub fn New(gameState: &GameState) -> Self {
+        let player = &gameState.player;
+        Self {
+            ticks: gameState.ticks,
+            playerLocation: (player.location.x(), player.location.y()),
+            playerViewDir: (player.viewDir.x(), player.viewDir.y()),
+            playerPitch: player.pitch,
+            playerHealth: player.health,
+            playerScore: player.score,
+            enemies: gameState.enemies.iter().map(|e| EnemyReport {
+                enemyCode: e.enemyType.code.clone(),
+                location: (e.location.x(), e.location.y()),
+                health: e.health,
+                state: e.currState
+            }).collect()
+        }
+    }
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ticks: {}", self.ticks)?;
+        writeln!(f, "player: location ({:.4}, {:.4}) view ({:.4}, {:.4}) pitch {:.4} health {} score {}",
+            self.playerLocation.0, self.playerLocation.1, self.playerViewDir.0, self.playerViewDir.1, self.playerPitch, self.playerHealth, self.playerScore)?;
+        for (i, e) in self.enemies.iter().enumerate() {
+            writeln!(f, "enemy {} {}: location ({:.4}, {:.4}) health {} {:?}", i, e.enemyCode, e.location.0, e.location.1, e.health, e.state)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+/*
+=========================================================
+    Demo recording & playback tests
+
+    Scripted inputs are stepped through the simulation while being recorded,
+    then the recording is played back headless and must land in exactly the
+    same state. Demos in tests/demos/ are replayed and their final state is
+    compared against the matching .expected report (regenerate these with
+    UPDATE_GOLDEN=1 after an intentional gameplay change).
+=========================================================
+*/
+
+use std::env;
+use std::fs;
+use crate::demo::{DemoHeader, DemoRecorder, SimulationReport};
+use crate::engine::PlayDemoHeadless;
+use crate::game_state::{GameState, TickInputs, moveCommand_t};
+use crate::utils::scratch::ScratchPath;
+
+const DEMO_DIR: &str = "tests/demos";
+const SCRATCH_DIR: &str = "target/demo_tests";
+
+// Walk up the spawn corridor, turn, strafe, shoot and try a door
+fn ScriptedTick(tick: usize) -> TickInputs {
+    let (mouseXrel, mouseYrel) = if (130..140).contains(&tick) { (-25, 4) } else { (0, 0) };
+    TickInputs {
+        moveCommand: match tick {
+            0..=119 => moveCommand_t::NORTH,
+            120..=199 => moveCommand_t::NORTH_WEST,
+            200..=259 => moveCommand_t::EAST,
+            _ => moveCommand_t::NONE
+        },
+        mouseXrel,
+        mouseYrel,
+        fireWeapon: (270..300).contains(&tick),
+        openDoor: (300..305).contains(&tick)
+    }
+}
+
+fn RecordScriptedRun(demoPath: &str, seed: u64, numTicks: usize) -> SimulationReport {
+    let header = DemoHeader { mapPath: "assets/map.wmap".to_string(), seed, verticalLook: true };
+    let mut recorder = DemoRecorder::New(demoPath, &header).unwrap();
+
+    let mut gameState = GameState::New(&header.mapPath, seed, header.verticalLook).unwrap();
+    for tick in 0..numTicks {
+        let inputs = ScriptedTick(tick);
+        recorder.Record(&inputs).unwrap();
+        gameState.Step(&inputs);
+    }
+
+    SimulationReport::New(&gameState)
+}
+
+#[test]
+fn recorded_run_replays_exactly() {
+    let demoPath = ScratchPath(SCRATCH_DIR, "scripted.csv");
+
+    let liveReport = RecordScriptedRun(&demoPath, 1234, 600);
+    let replayReport = PlayDemoHeadless(&demoPath).unwrap();
+
+    assert_eq!(liveReport, replayReport);
+    assert_ne!(liveReport.playerLocation, (22.5, 2.5), "scripted run should have moved the player");
+}
+
+#[test]
+fn same_seed_same_state() {
+    let first = RecordScriptedRun(&ScratchPath(SCRATCH_DIR, "seed_a.csv"), 99, 900);
+    let second = RecordScriptedRun(&ScratchPath(SCRATCH_DIR, "seed_b.csv"), 99, 900);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn checked_in_demos_reach_expected_state() {
+    let mut numDemos = 0;
+    for entry in fs::read_dir(DEMO_DIR).unwrap() {
+        let demoPath = entry.unwrap().path();
+        if demoPath.extension().is_none_or(|ext| ext != "csv") {
+            continue;
+        }
+        numDemos += 1;
+
+        let report = PlayDemoHeadless(demoPath.to_str().unwrap()).unwrap().to_string();
+        let expectedPath = demoPath.with_extension("expected");
+        if env::var("UPDATE_GOLDEN").is_ok() {
+            fs::write(&expectedPath, &report).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expectedPath)
+            .unwrap_or_else(|e| panic!("missing {} ({}) ; run with UPDATE_GOLDEN=1 to create it", expectedPath.display(), e));
+        assert_eq!(report, expected, "demo {} ended in a different state", demoPath.display());
+    }
+    assert!(numDemos > 0, "no demos found in {}", DEMO_DIR);
+}
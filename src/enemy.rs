@@ -2,32 +2,52 @@ use crate::multimedia::TextureType;
 use crate::tiles::{Sprite, TextureHandle};
 use crate::utils::vec2d::{Dot, iPoint2, Point2, RandomUnitVec, Vec2};
 use std::f64::consts::PI;
+use rand::Rng;
 use crate::animation::{AnimationClip, AnimationMagazine, AnimationReel};
+use crate::enemy_types::EnemyType;
 use crate::map::Map;
+use crate::map_file::EnemyRoutine;
+use crate::pathfinding::FindPath;
 use crate::player::Player;
 
-#[derive(PartialEq)]
-pub enum EnemyType {
-    GUARD,
-    OFFICER,
-    SS
-}
+// Hit points one pistol shot takes, whatever it hits ; how many shots an enemy takes comes from its type's health
+const PISTOL_DAMAGE: i32 = 20;
+
+// Ticks between working out a new path to the player while chasing
+const PATH_RECOMPUTE_TICKS: u32 = 30;
+
+// Chance per tick, out of 256, that a chasing enemy with the player in sight stops to shoot, divided by its distance in tiles
+const SHOOT_CHANCE: u32 = 16;
 
-#[derive(PartialEq)]
-enum EnemyState {
-    IDLE,
+// Clips in each enemy's animation magazine
+const CLIP_WALK: u32 = 0;       // 0 - 7, one per facing
+const CLIP_PAIN: u32 = 8;
+const CLIP_DIE: u32 = 9;
+const CLIP_STAND: u32 = 11;     // 11 - 18, one per facing
+const CLIP_SHOOT: u32 = 19;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum EnemyState {
+    STAND,
+    PATROL,
+    CHASE,
+    SHOOT,
     DAMAGE,
     DEAD
 }
 
 pub struct EnemyInputsBuffer {
-    pub damage: bool
+    pub damage: bool,
+
+    // Where a gunshot the enemy heard came from
+    pub noise: Option<Point2>
 }
 
 impl EnemyInputsBuffer {
     pub fn New() -> Self {
         Self {
-            damage: false
+            damage: false,
+            noise: None
         }
     }
 }
@@ -38,13 +58,23 @@ pub struct Enemy {
     pub tile: iPoint2,
     pub viewDir: Vec2,
     pub AM_enemySprites: AnimationMagazine,
-    currState: EnemyState,
+    pub currState: EnemyState,
     pub inputsBuffer: EnemyInputsBuffer,
     pub health: i32,
-    refreshRate: usize
+
+    // Counts down from spotting the player to reacting
+    reactionTicks: Option<u32>,
+    shotFired: bool,
+
+    // Where the enemy last heard the player, while it can't see them
+    noiseLocation: Option<Point2>,
+
+    // Tiles left to walk through to reach the player (or the noise), and ticks until they're worked out again
+    path: Vec<iPoint2>,
+    pathTicks: u32
 }
 
-fn GenerateEnemyAnimationMagazine(textureType: TextureType, refreshRate: usize) -> AnimationMagazine {
+fn GenerateEnemyAnimationMagazine(textureType: TextureType) -> AnimationMagazine {
     let mut AM = AnimationMagazine::New(Vec::new(), 0);
     // Clips 0 - 7
     for i in 0..8 {
@@ -59,7 +89,7 @@ fn GenerateEnemyAnimationMagazine(textureType: TextureType, refreshRate: usize)
                         TextureHandle::New(textureType, 33+i)
                     ],
                     0.3,
-                    0.02*(60.0/(refreshRate as f64)),
+                    0.02,
                     None
                 )
             )
@@ -74,7 +104,7 @@ fn GenerateEnemyAnimationMagazine(textureType: TextureType, refreshRate: usize)
                     TextureHandle::New(textureType, 41)
                 ],
                 0.3,
-                0.02*(60.0/(refreshRate as f64)),
+                0.02,
                 Some(0)
             )
         )
@@ -91,7 +121,7 @@ fn GenerateEnemyAnimationMagazine(textureType: TextureType, refreshRate: usize)
                     TextureHandle::New(textureType, 45)
                 ],
                 0.3,
-                0.045*(60.0/(refreshRate as f64)),
+                0.045,
                 Some(10)
             )
         )
@@ -104,61 +134,116 @@ fn GenerateEnemyAnimationMagazine(textureType: TextureType, refreshRate: usize)
         )
     );
 
+    // Clips 11 - 18 - standing (static), same facing order as walking
+    for i in 0..8 {
+        AM.clips.push(
+            AnimationClip::STATIC(
+                TextureHandle::New(textureType, 1+i)
+            )
+        );
+    }
+
+    // Clip 19 - shooting ; aim, fire, lower the weapon, then back to walking
+    AM.clips.push(
+        AnimationClip::REEL(
+            AnimationReel::New(
+                vec![
+                    TextureHandle::New(textureType, 49),
+                    TextureHandle::New(textureType, 50),
+                    TextureHandle::New(textureType, 51)
+                ],
+                0.3,
+                0.02,
+                Some(CLIP_WALK)
+            )
+        )
+    );
+
     return AM;
 }
 
 impl Enemy {
-    pub fn New(enemyType: EnemyType, location: Point2, tile: iPoint2, viewDir: Vec2, refreshRate: usize) -> Self {
-        let AM_enemySprites: AnimationMagazine =  match enemyType {
-            EnemyType::GUARD => {
-                GenerateEnemyAnimationMagazine(TextureType::GUARD, refreshRate)
-            },
-            EnemyType::OFFICER => {
-                GenerateEnemyAnimationMagazine(TextureType::OFFICER, refreshRate)
-            },
-            EnemyType::SS => {
-                GenerateEnemyAnimationMagazine(TextureType::SS, refreshRate)
+    pub fn New(enemyType: &EnemyType, location: Point2, tile: iPoint2, viewDir: Vec2, routine: EnemyRoutine) -> Self {
+        let mut AM_enemySprites: AnimationMagazine = GenerateEnemyAnimationMagazine(enemyType.sprites);
+
+        let currState = match routine {
+            EnemyRoutine::STAND => {
+                AM_enemySprites.currClipIndex = CLIP_STAND;
+                EnemyState::STAND
             },
+            EnemyRoutine::PATROL => EnemyState::PATROL
         };
 
         Self {
-            enemyType,
+            enemyType: enemyType.clone(),
             location,
             tile,
             viewDir,
             AM_enemySprites,
-            currState: EnemyState::IDLE,
+            currState,
             inputsBuffer: EnemyInputsBuffer::New(),
-            health: 150, 
-            refreshRate
+            health: enemyType.health,
+            reactionTicks: None,
+            shotFired: false,
+            noiseLocation: None,
+            path: Vec::new(),
+            pathTicks: 0
         }
     }
 
-    pub fn Update(&mut self, map: &Map, player: &Player) {
+    pub fn Update(&mut self, map: &mut Map, player: &mut Player, rng: &mut impl Rng) {
+        if self.inputsBuffer.damage {
+            self.inputsBuffer.damage = false;
+            if self.currState != EnemyState::DEAD {
+                self.currState = EnemyState::DAMAGE;
+            }
+        }
+        if let Some(noise) = self.inputsBuffer.noise.take() {
+            self.HearNoise(noise);
+        }
+
         match self.currState {
-            EnemyState::IDLE => {
-                if self.inputsBuffer.damage == true {
-                    self.inputsBuffer.damage = false;
-                    self.currState = EnemyState::DAMAGE;
-                } else if self.AM_enemySprites.currClipIndex < 8 {
-                    let proposedLocation = self.location + self.viewDir*0.01*(60.0/(self.refreshRate as f64));
-                    let proposedTileCoord = iPoint2::from(proposedLocation);
-                    if map.ValidEnemyLocation(proposedLocation, player.location) {
-                        self.location = proposedLocation;
-                        self.tile = proposedTileCoord;
-                    } else {
-                        self.viewDir = RandomUnitVec();
+            EnemyState::STAND => {
+                self.WatchForPlayer(map, player, rng);
+            },
+            EnemyState::PATROL => {
+                if self.AM_enemySprites.currClipIndex < 8 {
+                    // Walking into a door opens it and waits for it ; anything else sends the enemy off another way
+                    let proposedLocation = self.location + self.viewDir*self.enemyType.walkSpeed;
+                    if !self.MoveIfValid(proposedLocation, map, player) && !map.OpenDoor(iPoint2::from(proposedLocation)) {
+                        self.viewDir = RandomUnitVec(rng);
                     }
                 }
+                self.WatchForPlayer(map, player, rng);
+            },
+            EnemyState::CHASE => {
+                // Held in place while the pain frame shows
+                if self.AM_enemySprites.currClipIndex < 8 {
+                    self.Chase(map, player, rng);
+                }
+            },
+            EnemyState::SHOOT => {
+                if self.AM_enemySprites.currClipIndex != CLIP_SHOOT {
+                    self.currState = EnemyState::CHASE;
+                } else if self.AM_enemySprites.GetCurrFrameIndex() == 1 && !self.shotFired {
+                    self.shotFired = true;
+                    self.Fire(map, player, rng);
+                }
             },
             EnemyState::DAMAGE => {
-                self.health -= 20;
+                self.health -= PISTOL_DAMAGE;
                 if self.health > 0 {
-                    self.AM_enemySprites.currClipIndex = 8;
-                    self.currState = EnemyState::IDLE;
+                    // Getting shot gives the player away, whether or not the enemy had seen them
+                    self.AM_enemySprites.PlayClip(CLIP_PAIN);
+                    self.reactionTicks = None;
+                    self.currState = EnemyState::CHASE;
                 } else {
-                    self.AM_enemySprites.currClipIndex = 9;
+                    self.AM_enemySprites.PlayClip(CLIP_DIE);
                     self.currState = EnemyState::DEAD;
+                    player.score += self.enemyType.score;
+                    if let Some(objectID) = self.enemyType.dropItem {
+                        map.DropItem(self.tile, objectID);
+                    }
                 }
             },
             EnemyState::DEAD => {
@@ -169,6 +254,125 @@ impl Enemy {
         self.AM_enemySprites.Update();
     }
 
+    // In front of the enemy, with nothing solid in between
+    fn CanSeePlayer(&self, map: &Map, player: &Player) -> bool {
+        Dot(player.location - self.location, self.viewDir) > 0.0 && map.LineOfSight(self.location, player.location)
+    }
+
+    // Standing and patrolling enemies take a moment to react once the player comes into sight
+    fn WatchForPlayer(&mut self, map: &Map, player: &Player, rng: &mut impl Rng) {
+        match self.reactionTicks {
+            None => {
+                if self.CanSeePlayer(map, player) {
+                    self.reactionTicks = Some(rng.gen_range(1..=self.enemyType.maxReactionTicks));
+                }
+            },
+            Some(0) => {
+                self.StartChasing();
+            },
+            Some(ticks) => {
+                self.reactionTicks = Some(ticks - 1);
+            }
+        }
+    }
+
+    fn StartChasing(&mut self) {
+        self.reactionTicks = None;
+        self.currState = EnemyState::CHASE;
+        self.AM_enemySprites.PlayClip(CLIP_WALK);
+    }
+
+    // A gunshot sends standing and patrolling enemies straight after the player, with no reaction delay ; enemies
+    // already chasing head for the newest noise instead of wherever they were going. Only a noise from somewhere new
+    // needs a new path
+    fn HearNoise(&mut self, noise: Point2) {
+        match self.currState {
+            EnemyState::STAND | EnemyState::PATROL => self.StartChasing(),
+            EnemyState::DEAD => return,
+            _ => {}
+        }
+        if self.noiseLocation != Some(noise) {
+            self.noiseLocation = Some(noise);
+            self.pathTicks = 0;
+        }
+    }
+
+    // Follows a path to the player, recomputed every so often as they move, and now and then stops to shoot. An
+    // enemy that only heard the player goes to where the noise came from until it sees them, or gets there
+    fn Chase(&mut self, map: &mut Map, player: &Player, rng: &mut impl Rng) {
+        if let Some(noise) = self.noiseLocation {
+            if map.LineOfSight(self.location, player.location) || TileDistance(self.tile, iPoint2::from(noise)) <= 1 {
+                self.noiseLocation = None;
+                self.pathTicks = 0;
+            }
+        }
+        let goal = self.noiseLocation.unwrap_or(player.location);
+        let goalTile = iPoint2::from(goal);
+
+        // Worked out again at a fixed rate, even when there's no way through, as A* over the whole map isn't cheap
+        if self.pathTicks == 0 {
+            self.path = FindPath(map, self.tile, goalTile).unwrap_or_default();
+            self.pathTicks = PATH_RECOMPUTE_TICKS;
+        } else {
+            self.pathTicks -= 1;
+        }
+        if self.path.first() == Some(&self.tile) {
+            self.path.remove(0);
+        }
+
+        // Head for the middle of the next tile on the path, opening it if it's a door ; straight for the goal once next
+        // to it, or if there's no way through
+        let target = match self.path.first() {
+            Some(&nextTile) if nextTile != goalTile => {
+                map.OpenDoor(nextTile);
+                Point2::from(nextTile) + Vec2::New(0.5, 0.5)
+            },
+            _ => goal
+        };
+        self.viewDir = (target - self.location).UnitVector();
+        let step = self.viewDir*self.enemyType.runSpeed;
+        for proposedStep in [step, Vec2::New(step.x(), 0.0), Vec2::New(0.0, step.y())] {
+            if self.MoveIfValid(self.location + proposedStep, map, player) {
+                break;
+            }
+        }
+
+        if self.CanSeePlayer(map, player) {
+            let dist = TileDistance(self.tile, iPoint2::from(player.location));
+            let chance = if dist <= 1 { 256 } else { SHOOT_CHANCE/dist };
+            if rng.gen_range(0..256) < chance {
+                self.currState = EnemyState::SHOOT;
+                self.shotFired = false;
+                self.AM_enemySprites.PlayClip(CLIP_SHOOT);
+            }
+        }
+    }
+
+    // As in the original, the further away the player is, the less likely a hit is and the less it hurts ; better
+    // aim makes the player count as closer for the hit chance only
+    fn Fire(&mut self, map: &Map, player: &mut Player, rng: &mut impl Rng) {
+        if !map.LineOfSight(self.location, player.location) {
+            return;
+        }
+
+        let dist = TileDistance(self.tile, iPoint2::from(player.location)) as i32;
+        let hitChance = 256 - dist*16*100/self.enemyType.accuracy;
+        if rng.gen_range(0..256) < hitChance {
+            let maxDamage = if dist < 2 { self.enemyType.damage } else if dist < 4 { self.enemyType.damage/2 } else { self.enemyType.damage/4 };
+            player.TakeDamage(if maxDamage > 0 { rng.gen_range(0..maxDamage) } else { 0 });
+        }
+    }
+
+    fn MoveIfValid(&mut self, proposedLocation: Point2, map: &Map, player: &Player) -> bool {
+        if map.ValidEnemyLocation(proposedLocation, player.location) {
+            self.location = proposedLocation;
+            self.tile = iPoint2::from(proposedLocation);
+            true
+        } else {
+            false
+        }
+    }
+
     pub fn CalculateSprite(&mut self, playerViewDir: Vec2) -> Sprite {
         let enemyViewDir = self.viewDir;
         let enemyEastDir = self.viewDir.Rotate(-PI/2.0);
@@ -207,6 +411,8 @@ impl Enemy {
         let currClipIndex = self.AM_enemySprites.currClipIndex;
         if currClipIndex < 8 && angleCorrectClipIndex != currClipIndex {
             self.AM_enemySprites.SwitchClipIndexWithTimeCopy(angleCorrectClipIndex as usize);
+        } else if (CLIP_STAND..CLIP_STAND+8).contains(&currClipIndex) {
+            self.AM_enemySprites.currClipIndex = CLIP_STAND + angleCorrectClipIndex;
         }
 
         let textureHandle = self.AM_enemySprites.GetCurrTexture();
@@ -217,3 +423,8 @@ impl Enemy {
         }
     }
 }
+
+// Tiles apart, counting diagonal steps as one
+fn TileDistance(from: iPoint2, to: iPoint2) -> u32 {
+    (to.x() - from.x()).unsigned_abs().max((to.y() - from.y()).unsigned_abs())
+}
@@ -0,0 +1,150 @@
+/*
+=========================================================
+    Enemy AI tests
+
+    Small hand-written maps where the player stands still and an enemy is
+    pointed at or away from them, then the simulation is stepped to see the
+    enemy notice, chase and shoot. Enemies get a random facing when a map
+    loads, so each test sets the facing it needs.
+=========================================================
+*/
+
+use crate::enemy::EnemyState;
+use crate::game_state::{GameState, TickInputs};
+use crate::tiles::{DoorStatus, Tile};
+use crate::utils::scratch::WriteScratchFile;
+use crate::utils::vec2d::{iPoint2, Point2, Vec2};
+
+const SCRATCH_DIR: &str = "target/enemy_tests";
+
+// A corridor with the player at its west end and a standing guard at its east end, with a wall and door between them
+const CORRIDOR: &str = "\
+[map]
+name = Corridor
+width = 9
+height = 5
+
+[walls]
+15,15,15,15,15,15,15,15,15
+15,,,,15,,,,15
+15,,,,D,,,,15
+15,,,,15,,,,15
+15,15,15,15,15,15,15,15,15
+
+[actors]
+,,,,,,,,
+,,,,,,,,
+,P-E,,,,,,GU-S,
+,,,,,,,,
+,,,,,,,,
+";
+
+fn LoadCorridor(fileName: &str, contents: &str, guardViewDir: Vec2) -> GameState {
+    let path = WriteScratchFile(SCRATCH_DIR, fileName, contents);
+    let mut gameState = GameState::New(&path, 0, false).unwrap();
+    gameState.enemies[0].viewDir = guardViewDir;
+    gameState
+}
+
+fn StepFor(gameState: &mut GameState, numTicks: usize) {
+    for _ in 0..numTicks {
+        gameState.Step(&TickInputs::default());
+    }
+}
+
+#[test]
+fn standing_enemy_notices_only_a_player_in_front_and_in_sight() {
+    let west = Vec2::New(-1.0, 0.0);
+
+    // Door closed
+    let mut gameState = LoadCorridor("closed_door.wmap", CORRIDOR, west);
+    StepFor(&mut gameState, 120);
+    assert_eq!(gameState.enemies[0].currState, EnemyState::STAND);
+
+    // Door open, but the guard faces away
+    let mut gameState = LoadCorridor("facing_away.wmap", CORRIDOR, Vec2::New(1.0, 0.0));
+    if let Tile::DOOR(door) = gameState.map.GetMutTile(iPoint2::New(4, 2)) {
+        door.status = DoorStatus::OPENING;
+    }
+    StepFor(&mut gameState, 120);
+    assert_eq!(gameState.enemies[0].currState, EnemyState::STAND);
+
+    // Door open and the guard faces the player ; it reacts after a short delay
+    let mut gameState = LoadCorridor("in_sight.wmap", CORRIDOR, west);
+    if let Tile::DOOR(door) = gameState.map.GetMutTile(iPoint2::New(4, 2)) {
+        door.status = DoorStatus::OPENING;
+    }
+    StepFor(&mut gameState, 120);
+    assert_ne!(gameState.enemies[0].currState, EnemyState::STAND);
+}
+
+#[test]
+fn alerted_enemy_closes_in_and_shoots() {
+    let openCorridor = CORRIDOR.replace("15,,,,D,,,,15", "15,,,,,,,,15");
+    let mut gameState = LoadCorridor("open.wmap", &openCorridor, Vec2::New(-1.0, 0.0));
+
+    let mut statesSeen: Vec<EnemyState> = Vec::new();
+    for _ in 0..900 {
+        gameState.Step(&TickInputs::default());
+        statesSeen.push(gameState.enemies[0].currState);
+    }
+
+    assert!(statesSeen.contains(&EnemyState::CHASE));
+    assert!(statesSeen.contains(&EnemyState::SHOOT));
+    assert!(gameState.enemies[0].location.x() < 3.0, "guard should have run up to the player, ended at {}", gameState.enemies[0].location.x());
+    assert!(gameState.player.health < 100, "the player should have been hit at point blank range");
+}
+
+#[test]
+fn getting_shot_alerts_an_enemy_that_hadnt_seen_the_player() {
+    let mut gameState = LoadCorridor("shot.wmap", CORRIDOR, Vec2::New(1.0, 0.0));
+    gameState.enemies[0].inputsBuffer.damage = true;
+    StepFor(&mut gameState, 1);
+
+    assert_eq!((gameState.enemies[0].currState, gameState.enemies[0].health), (EnemyState::CHASE, 5));
+
+    // It opens the door between them on its way over, and the door stays open while it's in the doorway
+    let mut doorWasOccupiedWhileOpen = false;
+    for _ in 0..600 {
+        StepFor(&mut gameState, 1);
+        if let Tile::DOOR(door) = gameState.map.GetTile(iPoint2::New(4, 2)) {
+            if gameState.enemies[0].tile == iPoint2::New(4, 2) {
+                assert!(door.position <= 0.2, "door closed onto the guard");
+                doorWasOccupiedWhileOpen = true;
+            }
+        }
+    }
+    assert!(doorWasOccupiedWhileOpen, "guard never went through the door");
+    assert!(gameState.player.health < 100);
+}
+
+#[test]
+fn door_never_closes_on_an_enemy_in_the_doorway() {
+    // Facing away from the player, so it stays put
+    let mut gameState = LoadCorridor("doorway.wmap", CORRIDOR, Vec2::New(1.0, 0.0));
+    let doorTile = iPoint2::New(4, 2);
+    gameState.enemies[0].location = Point2::New(4.5, 2.5);
+    gameState.enemies[0].tile = doorTile;
+    let DoorStatusNow = |gameState: &GameState| match gameState.map.GetTile(doorTile) {
+        Tile::DOOR(door) => door.status.clone(),
+        _ => panic!()
+    };
+
+    // Caught closing, it opens again
+    if let Tile::DOOR(door) = gameState.map.GetMutTile(doorTile) {
+        door.status = DoorStatus::CLOSING;
+        door.position = 0.1;
+    }
+    StepFor(&mut gameState, 1);
+    assert!(DoorStatusNow(&gameState) == DoorStatus::OPENING);
+
+    // Held open however long the enemy stays
+    StepFor(&mut gameState, 600);
+    assert!(DoorStatusNow(&gameState) == DoorStatus::OPEN);
+
+    // And closes once it's gone
+    gameState.enemies[0].location = Point2::New(5.5, 2.5);
+    gameState.enemies[0].tile = iPoint2::New(5, 2);
+    StepFor(&mut gameState, 300);
+    assert!(DoorStatusNow(&gameState) == DoorStatus::CLOSED);
+}
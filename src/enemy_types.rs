@@ -0,0 +1,158 @@
+use crate::load_error::LoadError;
+use crate::multimedia::{TextureType, TEXTURE_SHEET_SPECS};
+use crate::utils::csv::ReadLines;
+use std::collections::HashMap;
+
+/*
+=========================================================
+    Enemy types
+
+    What each kind of enemy is like, read from a text file rather than
+    written into the code, so a mod can retune the guards or add new kinds
+    of enemy with their own map code. Maps use `assets/enemies.cfg` unless
+    their `[map]` section names another file with `enemies = <path>`.
+
+    One `[CODE]` section per type, the code being what the map's actors
+    layer places it with, then `key = value` lines for its stats ; the
+    Enemy Types section of README.md lists them. Every key but `drop` is
+    required.
+=========================================================
+*/
+
+pub const ENEMY_TYPES_PATH: &str = "assets/enemies.cfg";
+
+const ENEMY_TYPE_KEYS: [&str; 10] = ["name", "sprites", "health", "speed.walk", "speed.run", "reaction", "damage", "accuracy", "score", "drop"];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EnemyType {
+    pub code: String,
+    pub name: String,
+    pub sprites: TextureType,
+    pub health: i32,
+    pub walkSpeed: f64,
+    pub runSpeed: f64,
+    pub maxReactionTicks: u32,
+    pub damage: i32,
+    pub accuracy: i32,
+    pub score: u32,
+    pub dropItem: Option<i32>
+}
+
+pub struct EnemyTypes {
+    pub path: String,
+    types: Vec<EnemyType>
+}
+
+impl EnemyTypes {
+    // The file named by the map's `enemies` option, or the bundled one
+    pub fn FromMapHeader(header: &HashMap<String, String>) -> Result<Self, LoadError> {
+        Self::Load(header.get("enemies").map_or(ENEMY_TYPES_PATH, |path| path.as_str()))
+    }
+
+    pub fn Load(path: &str) -> Result<Self, LoadError> {
+        let contents = ReadLines(path)?;
+        let SyntaxError = |line: u64, message: String| LoadError::SYNTAX { path: path.to_string(), line, message };
+
+        let mut sections: Vec<TypeSection> = Vec::new();
+        for (i, text) in contents.lines().enumerate() {
+            let line = i as u64 + 1;
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(code) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                if !IsEnemyCode(code) {
+                    return Err(SyntaxError(line, format!("`[{}]` is not an enemy code ; use letters and digits, other than the map codes `P`, `W`, `O` and `D`", code)));
+                }
+                if sections.iter().any(|(_, seenCode, _)| seenCode == code) {
+                    return Err(SyntaxError(line, format!("enemy type `[{}]` appears twice", code)));
+                }
+                sections.push((line, code.to_string(), HashMap::new()));
+                continue;
+            }
+
+            let (key, value) = trimmed.split_once('=')
+                .ok_or_else(|| SyntaxError(line, format!("expected `key = value`, got `{}`", trimmed)))?;
+            let key = key.trim();
+            if !ENEMY_TYPE_KEYS.contains(&key) {
+                return Err(SyntaxError(line, format!("unknown key `{}` ; expected one of {}", key, ENEMY_TYPE_KEYS.join(", "))));
+            }
+            match sections.last_mut() {
+                Some((_, _, options)) => { options.insert(key.to_string(), (line, value.trim().to_string())); },
+                None => return Err(SyntaxError(line, "expected an enemy type section such as `[GU]` before any content".to_string()))
+            }
+        }
+
+        let mut types: Vec<EnemyType> = Vec::new();
+        for (sectionLine, code, options) in sections {
+            let Value = |key: &str| options.get(key).ok_or_else(|| SyntaxError(sectionLine, format!("[{}] needs a `{}`", code, key)));
+            let Number = |key: &str| -> Result<f64, LoadError> {
+                let (line, value) = Value(key)?;
+                value.parse::<f64>().ok().filter(|&number| number >= 0.0)
+                    .ok_or_else(|| SyntaxError(*line, format!("{}: `{}` is not a positive number", key, value)))
+            };
+            let WholeNumber = |key: &str| -> Result<i32, LoadError> {
+                let (line, value) = Value(key)?;
+                value.parse::<i32>().ok().filter(|&number| number >= 0)
+                    .ok_or_else(|| SyntaxError(*line, format!("{}: `{}` is not a positive whole number", key, value)))
+            };
+
+            let (spritesLine, sprites) = Value("sprites")?;
+            let sprites = match sprites.to_ascii_lowercase().as_str() {
+                "guard" => TextureType::GUARD,
+                "officer" => TextureType::OFFICER,
+                "ss" => TextureType::SS,
+                _ => return Err(SyntaxError(*spritesLine, format!("sprites: unknown sheet `{}` ; expected guard, officer or ss", sprites)))
+            };
+            let health = WholeNumber("health")?;
+            if health == 0 {
+                return Err(SyntaxError(Value("health")?.0, "health: an enemy needs at least 1 hit point".to_string()));
+            }
+
+            let dropItem = if options.contains_key("drop") { Some(WholeNumber("drop")?) } else { None };
+            let numObjects = TEXTURE_SHEET_SPECS.iter().find(|spec| spec.textureType == TextureType::OBJECT).unwrap().numTextures;
+            if let Some(objectID) = dropItem.filter(|&objectID| objectID < 1 || objectID > numObjects) {
+                return Err(SyntaxError(Value("drop")?.0, format!("drop: object {} is outside the object sheet's 1-{}", objectID, numObjects)));
+            }
+
+            types.push(EnemyType {
+                name: Value("name")?.1.clone(),
+                sprites,
+                health,
+                walkSpeed: Number("speed.walk")?,
+                runSpeed: Number("speed.run")?,
+                maxReactionTicks: WholeNumber("reaction")?.max(1) as u32,
+                damage: WholeNumber("damage")?,
+                accuracy: WholeNumber("accuracy")?.max(1),
+                score: WholeNumber("score")? as u32,
+                dropItem,
+                code
+            });
+        }
+
+        Ok(Self { path: path.to_string(), types })
+    }
+
+    pub fn Get(&self, code: &str) -> Option<&EnemyType> {
+        self.types.iter().find(|enemyType| enemyType.code == code)
+    }
+
+    // For error messages, e.g. `GU, OF or SS`
+    pub fn CodeList(&self) -> String {
+        let codes: Vec<&str> = self.types.iter().map(|enemyType| enemyType.code.as_str()).collect();
+        match codes.split_last() {
+            Some((last, [])) => last.to_string(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => "none".to_string()
+        }
+    }
+}
+
+// A type's section as read from the file ; the line of its `[CODE]`, its code, and each key's line and value
+type TypeSection = (u64, String, HashMap<String, (u64, String)>);
+
+// Anything the actors layer could hold that isn't a player start ; nor a wall, object or door, which CSV maps write in the same cells
+pub fn IsEnemyCode(code: &str) -> bool {
+    !code.is_empty() && !matches!(code, "P" | "W" | "O" | "D") && code.chars().all(|c| c.is_ascii_alphanumeric())
+}
@@ -0,0 +1,145 @@
+/*
+=========================================================
+    Enemy type tests
+
+    The bundled types file, broken ones, and a map bringing its own file
+    with a type of enemy the bundled one doesn't have.
+=========================================================
+*/
+
+use crate::enemy::EnemyState;
+use crate::enemy_types::{EnemyTypes, ENEMY_TYPES_PATH};
+use crate::game_state::{GameState, TickInputs};
+use crate::load_error::LoadError;
+use crate::multimedia::TextureType;
+use crate::tiles::Tile;
+use crate::utils::scratch::WriteScratchFile;
+use crate::utils::vec2d::iPoint2;
+
+const SCRATCH_DIR: &str = "target/enemy_types_tests";
+
+const ELITE_GUARD: &str = "\
+[EG]
+name = Elite guard
+sprites = guard
+health = 60
+speed.walk = 0.02
+speed.run = 0.06
+reaction = 5
+damage = 80
+accuracy = 200
+score = 250
+drop = 29
+";
+
+// A single room with the player and one enemy, which uses the types file at `ENEMIES`
+const ROOM: &str = "\
+[map]
+name = Room
+width = 5
+height = 3
+enemies = ENEMIES
+
+[walls]
+15,15,15,15,15
+15,,,,15
+15,15,15,15,15
+
+[actors]
+,,,,
+,P-E,,ENEMY,
+,,,,
+";
+
+fn WriteFile(fileName: &str, contents: &str) -> String {
+    WriteScratchFile(SCRATCH_DIR, fileName, contents)
+}
+
+fn LoadRoom(fileName: &str, enemiesPath: &str, enemyCode: &str) -> Result<GameState, LoadError> {
+    let mapPath = WriteFile(fileName, &ROOM.replace("ENEMIES", enemiesPath).replace("ENEMY", enemyCode));
+    GameState::New(&mapPath, 0, false)
+}
+
+#[test]
+fn bundled_types_differ_like_the_originals() {
+    let enemyTypes = EnemyTypes::Load(ENEMY_TYPES_PATH).unwrap();
+    let guard = enemyTypes.Get("GU").unwrap();
+    let officer = enemyTypes.Get("OF").unwrap();
+    let ss = enemyTypes.Get("SS").unwrap();
+
+    assert_eq!((guard.sprites, officer.sprites, ss.sprites), (TextureType::GUARD, TextureType::OFFICER, TextureType::SS));
+    assert!(guard.health < officer.health && officer.health < ss.health);
+    assert!(guard.runSpeed < ss.runSpeed && ss.runSpeed < officer.runSpeed);
+    assert!(officer.maxReactionTicks < guard.maxReactionTicks);
+    assert!(ss.accuracy > guard.accuracy);
+    assert!(guard.score < officer.score && officer.score < ss.score);
+    assert_ne!(guard.dropItem, ss.dropItem);
+    assert_eq!(enemyTypes.CodeList(), "GU, OF or SS");
+}
+
+#[test]
+fn broken_types_files_report_the_line() {
+    let AssertSyntaxError = |fileName: &str, contents: &str, expected: &str| {
+        let path = WriteFile(fileName, contents);
+        match EnemyTypes::Load(&path) {
+            Err(e @ LoadError::SYNTAX { .. }) => assert_eq!(e.to_string(), format!("{}:{}", path, expected)),
+            Err(e) => panic!("expected a syntax error, got: {}", e),
+            Ok(_) => panic!("expected a syntax error, but the file loaded")
+        }
+    };
+
+    AssertSyntaxError("missing_key.cfg", &ELITE_GUARD.replace("score = 250\n", ""), "1: [EG] needs a `score`");
+    AssertSyntaxError("bad_sheet.cfg", &ELITE_GUARD.replace("sprites = guard", "sprites = dog"), "3: sprites: unknown sheet `dog` ; expected guard, officer or ss");
+    AssertSyntaxError("bad_number.cfg", &ELITE_GUARD.replace("health = 60", "health = lots"), "4: health: `lots` is not a positive whole number");
+    AssertSyntaxError("bad_drop.cfg", &ELITE_GUARD.replace("drop = 29", "drop = 51"), "11: drop: object 51 is outside the object sheet's 1-50");
+    AssertSyntaxError("unknown_key.cfg", &ELITE_GUARD.replace("drop", "loot"), "11: unknown key `loot` ; expected one of name, sprites, health, speed.walk, speed.run, reaction, damage, accuracy, score, drop");
+    AssertSyntaxError("player_code.cfg", &ELITE_GUARD.replace("[EG]", "[P]"), "1: `[P]` is not an enemy code ; use letters and digits, other than the map codes `P`, `W`, `O` and `D`");
+    AssertSyntaxError("wall_code.cfg", &ELITE_GUARD.replace("[EG]", "[W]"), "1: `[W]` is not an enemy code ; use letters and digits, other than the map codes `P`, `W`, `O` and `D`");
+    AssertSyntaxError("twice.cfg", &format!("{}{}", ELITE_GUARD, ELITE_GUARD), "12: enemy type `[EG]` appears twice");
+}
+
+#[test]
+fn map_places_enemies_from_its_own_types_file() {
+    let enemiesPath = WriteFile("elite.cfg", ELITE_GUARD);
+    let gameState = LoadRoom("elite.wmap", &enemiesPath, "EG-S").unwrap();
+    let enemy = &gameState.enemies[0];
+    assert_eq!((enemy.enemyType.name.as_str(), enemy.health, enemy.currState), ("Elite guard", 60, EnemyState::STAND));
+
+    // The map's own file replaces the bundled one, so the original codes are unknown to it
+    match LoadRoom("no_guards.wmap", &enemiesPath, "GU") {
+        Err(e @ LoadError::CELL { .. }) => assert!(e.to_string().ends_with(&format!("cell `GU`: unknown enemy `GU` ; {} has EG", enemiesPath)), "{}", e),
+        _ => panic!("expected a cell error")
+    }
+
+    // Imported CSV maps name theirs in a header comment
+    let csvRoom = |enemyCode: &str| format!("# enemies = {}\nW-15,W-15,W-15,W-15,W-15\nW-15,P-E,,{},W-15\nW-15,W-15,W-15,W-15,W-15\n", enemiesPath, enemyCode);
+    let gameState = GameState::New(&WriteFile("elite.csv", &csvRoom("EG-S")), 0, false).unwrap();
+    assert_eq!((gameState.enemies[0].enemyType.code.as_str(), gameState.enemies[0].currState), ("EG", EnemyState::STAND));
+    match GameState::New(&WriteFile("no_guards.csv", &csvRoom("GU")), 0, false) {
+        Err(e @ LoadError::CELL { .. }) => assert!(e.to_string().ends_with(&format!("cell `GU`: unknown tile type `GU` ; expected W, O, D, P, an enemy from {} (EG) or an empty cell", enemiesPath)), "{}", e),
+        _ => panic!("expected a cell error")
+    }
+}
+
+#[test]
+fn killing_an_enemy_scores_and_drops_its_item() {
+    let enemiesPath = WriteFile("kill.cfg", ELITE_GUARD);
+    let mut gameState = LoadRoom("kill.wmap", &enemiesPath, "EG-S").unwrap();
+
+    // 60 hit points takes three pistol shots
+    let mut shotsTaken = 0;
+    while gameState.enemies[0].currState != EnemyState::DEAD {
+        gameState.enemies[0].inputsBuffer.damage = true;
+        gameState.Step(&TickInputs::default());
+        shotsTaken += 1;
+    }
+    assert_eq!((shotsTaken, gameState.player.score), (3, 250));
+
+    // The clip lies under the body, which still shows
+    let tile = gameState.enemies[0].tile;
+    assert_eq!(tile, iPoint2::New(3, 1));
+    match gameState.map.GetTile(tile) {
+        Tile::OBJECT(objectTile) => assert_eq!((objectTile.objectSprite.textureHandle.ID, objectTile.enemySprites.len()), (29, 1)),
+        _ => panic!("expected the dropped clip")
+    }
+}
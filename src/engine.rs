@@ -1,326 +1,227 @@
-use core::panic;
+use std::error::Error;
+use std::time::{Duration, Instant};
 
-use sdl2::{pixels::Color, rect::Rect};
 use crate::{
-    multimedia::{Multimedia, LightTexture, TextureType},
+    framebuffer::{Framebuffer, SoftwareTextures},
+    multimedia::{Multimedia, TextureSource},
     inputs_buffer::InputsBuffer,
-    player::Player,
-    map::Map,
-    utils::{
-        ray::Ray,
-        dda::RayCursor, vec2d::{Dot, Vec2, Point2, iPoint2}, conventions::TEXTURE_PITCH
-    }, tiles::{Tile, TextureHandle, Sprite, WallSlice}
+    player::PlayerPose,
+    game_state::GameState,
+    profiler::Profiler,
+    renderer::Renderer,
+    raycaster::{Raycaster, RenderResolution},
+    utils::{bmp::SaveBMP, conventions::TICK_RATE}
 };
-use crate::enemy::Enemy;
-use crate::utils::vec2d::iVec2;
+use crate::demo::{Demo, DemoHeader, DemoPlayback, DemoRecorder, SimulationReport};
+
+// Longest stretch of wall time simulated in one frame ; after a stall (window drag, breakpoint...) the game slows down rather than spiralling
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+// Startup options, filled in from the command line
+pub struct EngineSettings {
+    pub windowWidth: usize,
+    pub windowHeight: usize,
+    pub fov: f64,
+    pub mapPath: String,
+    pub textureSource: TextureSource,
+    pub verticalLook: bool,
+    pub renderResolution: RenderResolution,
+    pub showProfiler: bool,
+    pub profileCSVPath: Option<String>,
+    pub seed: Option<u64>,      // random if not given
+    pub recordDemoPath: Option<String>,
+    pub playDemoPath: Option<String>
+}
 
-struct SpriteRenderData {
-    vecToSprite: Vec2,
-    spriteHitDistY: f64,
-    spriteHitDistX: f64,
-    spriteScreenX: i32,
-    spriteRenderHeight: i32,
-    spriteScreenRect: Rect,
-    spriteTextureHandle: TextureHandle
+impl Default for EngineSettings {
+    fn default() -> Self {
+        Self {
+            windowWidth: 1280,
+            windowHeight: 720,
+            fov: 90.0,
+            mapPath: "assets/map.wmap".to_string(),
+            textureSource: TextureSource::SHEETS,
+            verticalLook: true,
+            renderResolution: RenderResolution::NATIVE,
+            showProfiler: false,
+            profileCSVPath: None,
+            seed: None,
+            recordDemoPath: None,
+            playDemoPath: None
+        }
+    }
 }
 
+// SDL front-end: turns window events into tick inputs, steps the GameState on a fixed clock and renders it
 pub struct GameEngine {
     pub multimedia: Multimedia,
     pub inputsBuffer: InputsBuffer,
-    pub player: Player,
-    pub map: Map,
+    pub gameState: GameState,
 
-    // Time related
-    doorMoveIncr: f64,
-    doorTimerIncr: f64,
-    playerMoveIncr: f64,
-    playerSwivelIncr: f64,
+    // Time related ; the simulation advances in fixed ticks, rendering interpolates the player between the last two
+    tickDuration: Duration,
+    tickAccumulator: Duration,
+    prevPlayerPose: PlayerPose,
 
     // Render related
-    wallSlicesBuffer: Vec<WallSlice>,
-    spritesBuffer: Vec<Sprite>,
-    spritesRenderDataBuffer: Vec<SpriteRenderData>,
-    wallRenderHeights: Vec<i32>,
-    spriteTileHitMap: Vec<Vec<bool>>,
-    weaponRenderTopLeft: iVec2,
-    weaponRenderPitch: i32,
+    raycaster: Raycaster,
+    profiler: Profiler,
 
-    // Enemy related
-    enemies: Vec<Enemy>
+    // Demos ; while playing one back, its recorded inputs replace the live ones each tick
+    demoRecorder: Option<DemoRecorder>,
+    demoPlayback: Option<DemoPlayback>
 }
 
 impl GameEngine {
-    pub fn Init(windowWidth: usize, windowHeight: usize, fov: f64, mapCSVPath: &str) -> Self {
-        let multimedia = Multimedia::New(windowWidth, windowHeight, fov);
-        let inputsBuffer = InputsBuffer{windowLock: true, ..Default::default()};
-        let player = Player::New(Point2::New(22.5, 2.5), multimedia.displayParams.refreshRate);
-        let (map, enemies): (Map, Vec<Enemy>) = Map::LoadFromCSV(mapCSVPath, multimedia.displayParams.refreshRate);
-        
-        let refreshRatePropr = multimedia.displayParams.refreshRate as f64 / 60.0;
-        let doorMoveIncr = 0.02/refreshRatePropr;
-        let doorTimerIncr = 0.01/refreshRatePropr;
-        let playerMoveIncr = 0.08/refreshRatePropr;
-        let playerSwivelIncr = 0.00125/refreshRatePropr;
-
-        let wallRenderHeights: Vec<i32> = vec![0; multimedia.windowParams.width];
-
-        let spriteTileHitMap: Vec<Vec<bool>> = vec![vec![false; map.height as usize]; map.width as usize];
-
-        let weaponRenderPitch = (windowWidth/2) as i32;
-        let weaponRenderX = (windowWidth/2) - (weaponRenderPitch/2) as usize;
-        let weaponRenderY = windowHeight - weaponRenderPitch as usize;
-
-        Self {
+    pub fn Init(settings: &EngineSettings) -> Result<Self, Box<dyn Error>> {
+        // A demo being played back dictates the map, seed and look mode it was recorded with
+        let demoPlayback = match &settings.playDemoPath {
+            Some(path) => Some(DemoPlayback::New(Demo::Load(path)?)),
+            None => None
+        };
+        let (mapPath, seed, verticalLook) = match &demoPlayback {
+            Some(playback) => (playback.demo.header.mapPath.clone(), playback.demo.header.seed, playback.demo.header.verticalLook),
+            None => (settings.mapPath.clone(), settings.seed.unwrap_or_else(rand::random), settings.verticalLook)
+        };
+        println!("seed: {}", seed);
+
+        let gameState = GameState::New(&mapPath, seed, verticalLook)?;
+        let softwareTextures = SoftwareTextures::Load(&settings.textureSource)?;
+        let multimedia = Multimedia::New(settings.windowWidth, settings.windowHeight, &softwareTextures)?;
+        let inputsBuffer = InputsBuffer{windowLock: true, verticalLook, ..Default::default()};
+
+        let demoRecorder = match (&settings.recordDemoPath, &demoPlayback) {
+            (Some(path), None) => {
+                let header = DemoHeader { mapPath, seed, verticalLook };
+                Some(DemoRecorder::New(path, &header).map_err(|e| format!("{}: {}", path, e))?)
+            },
+            _ => None
+        };
+        let prevPlayerPose = gameState.player.GetPose();
+
+        let mut raycaster = Raycaster::WithTextures(settings.windowWidth, settings.windowHeight, settings.fov, &gameState.map, softwareTextures);
+        raycaster.SetRenderResolution(settings.renderResolution);
+        let profiler = Profiler::New(settings.showProfiler, settings.profileCSVPath.as_deref())?;
+
+        Ok(Self {
             multimedia,
             inputsBuffer,
-            player,
-            map,
-
-            doorMoveIncr,
-            doorTimerIncr,
-            playerMoveIncr,
-            playerSwivelIncr,
-
-            wallSlicesBuffer: Vec::new(),
-            spritesBuffer: Vec::new(),
-            spritesRenderDataBuffer: Vec::new(),
-            wallRenderHeights,
+            gameState,
 
-            spriteTileHitMap,
+            tickDuration: Duration::from_secs(1) / TICK_RATE,
+            tickAccumulator: Duration::ZERO,
+            prevPlayerPose,
 
-            weaponRenderTopLeft: iVec2::New(weaponRenderX as i32, weaponRenderY as i32),
-            weaponRenderPitch,
+            raycaster,
+            profiler,
 
-            enemies
-        }
+            demoRecorder,
+            demoPlayback
+        })
     }
 
     pub fn GameLoop(&mut self) {
+        let mut prevFrameStart = Instant::now();
         loop {
+            let frameStart = Instant::now();
+            self.tickAccumulator += (frameStart - prevFrameStart).min(MAX_FRAME_TIME);
+            prevFrameStart = frameStart;
+
+            self.profiler.BeginUpdate();
             self.Update();
+            self.profiler.EndUpdate();
             if self.inputsBuffer.quit { break; }
             self.RenderFrame();
+            self.profiler.EndFrame(&self.raycaster.stats);
         }
     }
 
+    // Polls inputs once, then runs as many fixed ticks as the elapsed time calls for (possibly none)
     fn Update(&mut self) {
         self.inputsBuffer.Update(&mut self.multimedia.sdlContexts.sdlContext, &mut self.multimedia.sdlEventPump);
-        self.UpdateEnemies();
-        self.player.Update(&self.inputsBuffer, &mut self.map, &mut self.enemies, self.playerMoveIncr, self.playerSwivelIncr);
-        self.map.UpdateDoors(self.doorMoveIncr, self.doorTimerIncr, self.player.location);
-    }
+        self.UpdateWindow();
 
-    fn RenderFrame(&mut self) {
-        self.multimedia.sdlCanvas.clear();
-        self.DrawCeilingAndFloor();
-        self.RenderIntoBuffers();
-        self.DrawWallsFromBuffer();
-        self.DrawSpritesFromBuffer();
-        self.DrawWeapon();
-        self.multimedia.sdlCanvas.present();
-    }
-
-    fn DrawCeilingAndFloor(&mut self) {
-        self.multimedia.sdlCanvas.set_draw_color(Color::RGBA(50, 50, 50, 255));
-        self.multimedia.sdlCanvas.fill_rect(Rect::new(0, 0, self.multimedia.windowParams.width as u32, (self.multimedia.windowParams.height/2) as u32)).unwrap();
-
-        self.multimedia.sdlCanvas.set_draw_color(Color::RGBA(96, 96, 96, 255));
-        self.multimedia.sdlCanvas.fill_rect(Rect::new(0, (self.multimedia.windowParams.height / 2) as i32, self.multimedia.windowParams.width as u32, (self.multimedia.windowParams.height/2) as u32)).unwrap();
-    }
-
-    fn RenderIntoBuffers(&mut self) {
-        self.wallSlicesBuffer.clear();
-        self.spritesBuffer.clear();
-        self.ResetSpriteTileHitMap();
-
-        for x in 0..self.multimedia.windowParams.width {
-            let currRay = Ray::New(self.player.location, self.player.viewDir.Rotate(self.multimedia.renderParams.castingRayAngles[x].0));
-            let mut rayCursor = RayCursor::New(currRay, self.player.location);
-            let mut prevTileCoord = rayCursor.hitTile;
-            while self.map.WithinMap(rayCursor.hitTile) {
-                let prevTileWasDoor = if let Tile::DOOR(_) = self.map.GetTile(prevTileCoord) { true } else { false };
-                rayCursor.GoToNextHit();
-                let currTileCoord = rayCursor.hitTile;
-                prevTileCoord = currTileCoord;                
-
-                match self.map.GetTile(currTileCoord) {
-                    Tile::WALL(wall) => {
-                        let mut wallSlice = wall.GetWallSlice(&mut rayCursor);
-                        if prevTileWasDoor {
-                            let gateSidewall_lit = TextureHandle::New(TextureType::WALL, 101);
-                            let gateSideWall_unlit = TextureHandle::New(TextureType::WALL, 102);
-                            wallSlice.textureHandle = LightTexture(&mut rayCursor, gateSidewall_lit, gateSideWall_unlit);
-                        }
-                        self.wallSlicesBuffer.push(wallSlice);
-                        break;
-                    },
-                    Tile::DOOR(door) => {
-                        let doorWallSlice = door.GetWallSlice(&mut rayCursor);
-                        if doorWallSlice.is_some() {
-                            self.wallSlicesBuffer.push(doorWallSlice.unwrap());
-                            break;
-                        } else {
-                            continue;
-                        }
-                    },
-                    Tile::OBJECT(_) | Tile::EMPTY(_) => {
-                        self.GrabSprites(currTileCoord)
-                    },
-                    Tile::NONE => panic!(),
-                };
-            }
+        while self.tickAccumulator >= self.tickDuration && !self.inputsBuffer.quit {
+            self.prevPlayerPose = self.gameState.player.GetPose();
+            self.Tick();
+            self.tickAccumulator -= self.tickDuration;
         }
     }
 
-    fn DrawWallsFromBuffer(&mut self) {
-        self.ResetWallRenderHeights();
-        for x in 0..self.wallSlicesBuffer.len() {
-            let wallSlice = &self.wallSlicesBuffer[x];
-
-            let distToHitPoint = wallSlice.dist;
-            let renderHeight = (self.multimedia.renderParams.renderHeightProprConst / (distToHitPoint * self.multimedia.renderParams.castingRayAngles[x as usize].1)) as i32;
-            let screenY = (self.multimedia.windowParams.height/2) as i32 - (renderHeight / 2);
-            let screenRect = Rect::new(x as i32, screenY, 1, renderHeight as u32);
-            self.wallRenderHeights[x as usize] = renderHeight;
-
-            let texture = self.multimedia.assets.GetTexture(wallSlice.textureHandle);
-
-            // Render onto screen
-            let _ = self.multimedia.sdlCanvas.copy(texture, wallSlice.textureRect, screenRect);
-        }
-    }
-
-    fn DrawSpritesFromBuffer(&mut self) {
-        self.spritesRenderDataBuffer.clear();
-        for sprite in &self.spritesBuffer {            
-            let vecToSprite = sprite.location - self.player.location;
-            let spriteHitDistY = Dot(vecToSprite, self.player.viewDir);
-            let spriteHitDistX = Dot(vecToSprite, self.player.east);
-            let spriteScreenX = ((self.multimedia.windowParams.width/2) as f64 + ((self.multimedia.renderParams.projPlaneDist/spriteHitDistY)*spriteHitDistX)) as i32;
-            let spriteRenderHeight = (self.multimedia.renderParams.renderHeightProprConst / spriteHitDistY) as i32;
-            let spriteScreenRect = Rect::new(spriteScreenX - (spriteRenderHeight/2), (self.multimedia.windowParams.height as i32)/2 - (spriteRenderHeight/2), spriteRenderHeight as u32, spriteRenderHeight as u32);
-            let spriteTextureHandle = sprite.textureHandle;
-
-            self.spritesRenderDataBuffer.push(SpriteRenderData {
-                vecToSprite,
-                spriteHitDistY,
-                spriteHitDistX,
-                spriteScreenX,
-                spriteRenderHeight,
-                spriteScreenRect,
-                spriteTextureHandle
-            });
-        }
-
-        self.spritesRenderDataBuffer.sort_by(|a, b| a.spriteRenderHeight.partial_cmp(&b.spriteRenderHeight).unwrap());
-
-        for s in &self.spritesRenderDataBuffer {
-            for x in s.spriteScreenRect.x..(s.spriteScreenRect.x+s.spriteScreenRect.w) {
-                if x < 0 {
-                    continue;
-                } else if x >= self.multimedia.windowParams.width as i32 {
-                    break;
-                } else {
-                    if self.wallRenderHeights[x as usize] <= s.spriteRenderHeight {
-                        let spriteTextureWidthPercent = (x - s.spriteScreenRect.x) as f64 / (s.spriteScreenRect.w) as f64;
-                        let spriteTextureX = (spriteTextureWidthPercent * TEXTURE_PITCH as f64) as i32;
-                        let spriteTextureRect = Rect::new(spriteTextureX, 0, 1, TEXTURE_PITCH);
-                        let screenRect = Rect::new(x, s.spriteScreenRect.y, 1, s.spriteScreenRect.h as u32);
-                        
-                        let texture = self.multimedia.assets.GetTexture(s.spriteTextureHandle);
-
-                        let _ = self.multimedia.sdlCanvas.copy(texture, spriteTextureRect, screenRect);
-                    }
+    fn Tick(&mut self) {
+        let inputs = match &mut self.demoPlayback {
+            Some(playback) => match playback.Next() {
+                Some(inputs) => inputs,
+                None => {
+                    self.inputsBuffer.quit = true;
+                    return;
                 }
+            },
+            None => self.inputsBuffer.TickInputs()
+        };
+        // A demo that can't be written any more is dropped, keeping the ticks recorded so far ; the game carries on
+        if let Some(recorder) = &mut self.demoRecorder {
+            if let Err(e) = recorder.Record(&inputs) {
+                eprintln!("warning: {}: {} ; demo recording stopped", recorder.path, e);
+                self.demoRecorder = None;
             }
         }
-    }
 
-    fn DrawWeapon(&mut self) {
-        let screenRect = Rect::new(self.weaponRenderTopLeft.x(), self.weaponRenderTopLeft.y(), self.weaponRenderPitch as u32, self.weaponRenderPitch as u32);
-        let textureHandle = self.player.AM_weapon.GetCurrTexture();
-        let texture = self.multimedia.assets.GetTexture(textureHandle);
-        let textureRect = Rect::new(0, 0, TEXTURE_PITCH, TEXTURE_PITCH);
-        let _ = self.multimedia.sdlCanvas.copy(texture, textureRect, screenRect);
+        self.gameState.Step(&inputs);
+        self.inputsBuffer.ConsumeMouseMotion();
     }
 
-    fn ResetSpriteTileHitMap(&mut self) {
-        for x in 0..self.map.width {
-            for y in 0..self.map.height {
-                self.spriteTileHitMap[x as usize][y as usize] = false;
-            }
+    fn UpdateWindow(&mut self) {
+        if self.inputsBuffer.toggleFullscreen {
+            self.multimedia.ToggleFullscreen();
         }
-    }
-
-    fn ResetWallRenderHeights(&mut self) {
-        for i in 0..self.wallRenderHeights.len() {
-            self.wallRenderHeights[i] = 0;
+        if self.inputsBuffer.toggleFullscreen || self.inputsBuffer.windowResized {
+            let windowParams = self.multimedia.UpdateWindowParams();
+            self.raycaster.Resize(windowParams.width, windowParams.height);
+        }
+        if self.inputsBuffer.toggleProfiler {
+            self.profiler.showOverlay = !self.profiler.showOverlay;
+        }
+        if self.inputsBuffer.cycleRenderResolution {
+            self.raycaster.SetRenderResolution(self.raycaster.renderResolution.Next());
         }
     }
 
-    fn GrabSprites(&mut self, tileCoord: iPoint2) {
-        let currTileX = tileCoord.x() as usize;
-        let currTileY = tileCoord.y() as usize;
-        if self.spriteTileHitMap[currTileX][currTileY] == false {
-            match self.map.GetMutTile(tileCoord) {
-                Tile::OBJECT(objectTile) => {
-                    self.spritesBuffer.push(objectTile.objectSprite);
-                    if objectTile.IsEnemyHolder() {
-                        let spritesArr = objectTile.GetSprites();
-                        if spritesArr.is_some() {
-                            for s in spritesArr.unwrap() {
-                                self.spritesBuffer.push(*s);
-                            }
-                        }
-                    }
-                },
-                Tile::EMPTY(emptyTile) => {
-                    let spritesArr = emptyTile.GetSprites();
-                    if spritesArr.is_some() {
-                        for s in spritesArr.unwrap() {
-                            self.spritesBuffer.push(*s);
-                        }
-                    }
-                }
-                _ => panic!()
-            }
-            self.spriteTileHitMap[currTileX][currTileY] = true;
-        }
+    fn RenderFrame(&mut self) {
+        // Draw the player part way between the last two ticks, by how far into the next tick we are
+        let alpha = self.tickAccumulator.as_secs_f64() / self.tickDuration.as_secs_f64();
+        // A separate pose, so drawing never touches the simulation
+        let player = &self.gameState.player;
+        let pose = PlayerPose::Lerp(&self.prevPlayerPose, &player.GetPose(), alpha);
+        self.raycaster.RenderFrame(&mut self.multimedia, &pose, player, &self.gameState.map);
+
+        self.profiler.DrawOverlay(&mut self.multimedia);
+        self.multimedia.Present();
     }
+}
 
-    fn UpdateEnemies(&mut self) {
-        // Wipe all enemy sprites from tiles
-        for e in &self.enemies {
-            match self.map.GetMutTile(e.tile) {
-                Tile::OBJECT(object) => {
-                    object.enemySprites.clear();
-                },
-                Tile::EMPTY(emptyTile) => {
-                    emptyTile.enemySprites.clear();
-                },
-                Tile::NONE => panic!(),
-                _ => {}
-            }
-        }
+// Renders the player's spawn view into a software framebuffer and writes it out as a BMP ; no window or display needed
+pub fn RenderHeadlessFrame(windowWidth: usize, windowHeight: usize, fov: f64, mapPath: &str, outputBMPPath: &str) -> Result<(), Box<dyn Error>> {
+    let gameState = GameState::New(mapPath, 0, true)?;
 
-        // For each enemy...
-        for e in &mut self.enemies {
+    let mut raycaster = Raycaster::New(windowWidth, windowHeight, fov, &gameState.map);
+    let mut framebuffer = Framebuffer::New(windowWidth, windowHeight);
+    raycaster.RenderFrame(&mut framebuffer, &gameState.player.GetPose(), &gameState.player, &gameState.map);
+    framebuffer.Present();
 
-            e.Update(&self.map, &self.player);
+    SaveBMP(outputBMPPath, &framebuffer.frame)?;
+    Ok(())
+}
 
-            // Calculate and inject sprites into appropriate tiles
-            let tileCoord: iPoint2 = e.location.into();
-            let sprite = e.CalculateSprite(self.player.viewDir);
-            match self.map.GetMutTile(tileCoord) {
-                Tile::OBJECT(object) => {
-                    object.enemySprites.push(sprite);
-                },
-                Tile::EMPTY(emptyTile) => {
-                    emptyTile.enemySprites.push(sprite);
-                },
-                _ => {}
-            }
-        }
+// Replays a demo without a window, as fast as possible, and reports where everything ended up
+pub fn PlayDemoHeadless(demoPath: &str) -> Result<SimulationReport, Box<dyn Error>> {
+    let demo = Demo::Load(demoPath)?;
+    let mut gameState = GameState::New(&demo.header.mapPath, demo.header.seed, demo.header.verticalLook)?;
+
+    let mut playback = DemoPlayback::New(demo);
+    while let Some(inputs) = playback.Next() {
+        gameState.Step(&inputs);
     }
-}
 
+    Ok(SimulationReport::New(&gameState))
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use sdl2::{pixels::Color, rect::Rect};
+use crate::lighting::Shade;
+use crate::load_error::LoadError;
+use crate::multimedia::{TextureSource, TextureType, TEXTURE_SHEET_SPECS, COLOR_KEY};
+use crate::renderer::Renderer;
+use crate::tiles::TextureHandle;
+use crate::utils::bmp::{Bitmap, LoadBMP};
+use crate::utils::conventions::TEXTURE_PITCH;
+use crate::vswap::{LoadPalette, VSWAP};
+
+/*
+=========================================================
+    Framebuffer struct definition
+=========================================================
+*/
+
+// Pure software RGBA render target ; needs no SDL window, so frames can be produced on headless machines
+pub struct Framebuffer {
+    pub frame: Bitmap,
+    textures: SoftwareTextures
+}
+
+impl Framebuffer {
+    pub fn New(width: usize, height: usize) -> Self {
+        Self::WithTextures(width, height, SoftwareTextures::New())
+    }
+
+    pub fn WithTextures(width: usize, height: usize, textures: SoftwareTextures) -> Self {
+        Self {
+            frame: Bitmap {
+                width,
+                height,
+                pixels: vec![[0, 0, 0, 255]; width*height]
+            },
+            textures
+        }
+    }
+
+    // Changes the frame size while keeping the already loaded textures
+    pub fn Resize(&mut self, width: usize, height: usize) {
+        self.frame = Bitmap {
+            width,
+            height,
+            pixels: vec![[0, 0, 0, 255]; width*height]
+        };
+    }
+}
+
+// Clips a screen-space span [start, start+len) to [0, limit)
+fn ClipSpan(start: i32, len: i32, limit: usize) -> (i32, i32) {
+    (start.max(0), (start + len).min(limit as i32))
+}
+
+// Nearest-neighbour scaled copy of srcRect of src into dstRect of dst, shaded ; optionally skips fully transparent texels
+fn BlitScaled(dst: &mut Bitmap, src: &Bitmap, srcRect: Rect, dstRect: Rect, skipTransparent: bool, shade: Shade) {
+    let (dstW, dstH) = (dstRect.width() as i64, dstRect.height() as i64);
+    if dstW == 0 || dstH == 0 {
+        return;
+    }
+
+    let (x0, x1) = ClipSpan(dstRect.x(), dstW as i32, dst.width);
+    let (y0, y1) = ClipSpan(dstRect.y(), dstH as i32, dst.height);
+
+    for y in y0..y1 {
+        let texelY = srcRect.y() as i64 + ((y - dstRect.y()) as i64 * srcRect.height() as i64) / dstH;
+        for x in x0..x1 {
+            let texelX = srcRect.x() as i64 + ((x - dstRect.x()) as i64 * srcRect.width() as i64) / dstW;
+            if texelX as usize >= src.width || texelY as usize >= src.height {
+                continue;
+            }
+            let texel = src.GetPixel(texelX as usize, texelY as usize);
+            if !skipTransparent || texel[3] != 0 {
+                dst.pixels[y as usize*dst.width + x as usize] = shade.Apply(texel);
+            }
+        }
+    }
+}
+
+/*
+=========================================================
+    SoftwareTextures struct definition
+=========================================================
+*/
+
+// CPU-side copies of every texture sheet, for renderers and passes that sample texels directly ; the SDL assets are uploaded from these too
+#[derive(Clone)]
+pub struct SoftwareTextures {
+    sheets: HashMap<TextureType, Vec<Bitmap>>
+}
+
+impl SoftwareTextures {
+    // The BMP sheets that ship with the game ; for tests and headless rendering, where a missing sheet is a bug
+    pub fn New() -> Self {
+        Self::Load(&TextureSource::SHEETS).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn Load(source: &TextureSource) -> Result<Self, LoadError> {
+        let sheets = match source {
+            TextureSource::SHEETS => {
+                let mut sheets: HashMap<TextureType, Vec<Bitmap>> = HashMap::new();
+                for spec in &TEXTURE_SHEET_SPECS {
+                    sheets.insert(spec.textureType, ExtractTextures(spec.filename, spec.sheetPitch, spec.numTextures, spec.colorKey)?);
+                }
+                sheets
+            },
+            TextureSource::VSWAP { vswapPath, palettePath } => VSWAP::Load(vswapPath, &LoadPalette(palettePath)?)?.TextureSheets()
+        };
+
+        Ok(Self {
+            sheets
+        })
+    }
+
+    pub fn GetTexture(&self, textureHandle: TextureHandle) -> &Bitmap {
+        &self.sheets[&textureHandle.textureType][(textureHandle.ID-1) as usize]
+    }
+
+    pub fn GetSheet(&self, textureType: TextureType) -> &[Bitmap] {
+        &self.sheets[&textureType]
+    }
+}
+
+// Cuts a texture sheet BMP into TEXTURE_PITCH x TEXTURE_PITCH textures laid out in a grid, sheetPitch textures per row
+fn ExtractTextures(filename: &str, sheetPitch: i32, numTextures: i32, colorKey: bool) -> Result<Vec<Bitmap>, LoadError> {
+    let sheet = LoadBMP(filename)?;
+    let pitch = TEXTURE_PITCH as usize;
+
+    let mut textures: Vec<Bitmap> = Vec::new();
+    for textureID in 1..=numTextures {
+        let textureX = (((textureID - 1) % sheetPitch) as usize) * pitch;
+        let textureY = (((textureID - 1) / sheetPitch) as usize) * pitch;
+
+        let mut texels: Vec<[u8; 4]> = vec![[0, 0, 0, 0]; pitch*pitch];
+        for y in 0..pitch {
+            for x in 0..pitch {
+                let (sheetX, sheetY) = (textureX + x, textureY + y);
+                if sheetX >= sheet.width || sheetY >= sheet.height {
+                    continue;
+                }
+                let texel = sheet.GetPixel(sheetX, sheetY);
+                let keyedOut = colorKey && texel[0] == COLOR_KEY.r && texel[1] == COLOR_KEY.g && texel[2] == COLOR_KEY.b;
+                texels[y*pitch + x] = if keyedOut { [0, 0, 0, 0] } else { [texel[0], texel[1], texel[2], 255] };
+            }
+        }
+
+        textures.push(Bitmap {
+            width: pitch,
+            height: pitch,
+            pixels: texels
+        });
+    }
+
+    Ok(textures)
+}
+
+/*
+=========================================================
+    Renderer implementation
+=========================================================
+*/
+
+impl Renderer for Framebuffer {
+    fn Clear(&mut self) {
+        self.frame.pixels.fill([0, 0, 0, 255]);
+    }
+
+    fn FillRect(&mut self, color: Color, rect: Rect) {
+        let (x0, x1) = ClipSpan(rect.x(), rect.width() as i32, self.frame.width);
+        let (y0, y1) = ClipSpan(rect.y(), rect.height() as i32, self.frame.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                self.frame.pixels[y as usize*self.frame.width + x as usize] = [color.r, color.g, color.b, 255];
+            }
+        }
+    }
+
+    fn CopyTexture(&mut self, textureHandle: TextureHandle, srcRect: Rect, dstRect: Rect, shade: Shade) {
+        BlitScaled(&mut self.frame, self.textures.GetTexture(textureHandle), srcRect, dstRect, true, shade);
+    }
+
+    fn DrawBitmap(&mut self, bitmap: &Bitmap, dstRect: Rect) {
+        BlitScaled(&mut self.frame, bitmap, Rect::new(0, 0, bitmap.width as u32, bitmap.height as u32), dstRect, false, Shade::FULL_BRIGHT);
+    }
+
+    fn Present(&mut self) {
+        // Nothing to flip ; the finished frame lives in self.frame
+    }
+}
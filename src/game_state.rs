@@ -0,0 +1,135 @@
+use rand::{rngs::StdRng, SeedableRng};
+use crate::enemy::{Enemy, EnemyState};
+use crate::load_error::LoadError;
+use crate::map::Map;
+use crate::player::{Player, PlayerSpeeds};
+use crate::tiles::Tile;
+use crate::utils::vec2d::iPoint2;
+
+// Per-tick simulation speeds
+const DOOR_MOVE_INCR: f64 = 0.02;
+const DOOR_TIMER_INCR: f64 = 0.01;
+const PLAYER_SPEEDS: PlayerSpeeds = PlayerSpeeds {
+    moveIncr: 0.08,
+
+    // Per mouse count ; mouse motion is applied once per frame's worth of ticks, so these don't depend on the tick rate
+    swivelIncr: 0.00125,
+    pitchIncr: 0.0015
+};
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum moveCommand_t {
+    NORTH,
+    SOUTH,
+    EAST,
+    WEST,
+    NORTH_EAST,
+    NORTH_WEST,
+
+    #[default]
+    NONE
+}
+
+/*
+=========================================================
+    TickInputs struct definition
+=========================================================
+*/
+
+// Everything a single tick reads from the outside world ; filled in from SDL events by InputsBuffer, from a file by demo playback, or directly by tests
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TickInputs {
+    pub moveCommand: moveCommand_t,
+    pub mouseXrel: i32,
+    pub mouseYrel: i32,
+    pub openDoor: bool,
+    pub fireWeapon: bool
+}
+
+/*
+=========================================================
+    GameState struct definition
+=========================================================
+*/
+
+// The whole simulation, with no window, renderer or SDL context ; advanced one fixed tick at a time
+pub struct GameState {
+    pub player: Player,
+    pub map: Map,
+    pub enemies: Vec<Enemy>,
+    pub verticalLook: bool,
+    pub ticks: usize,
+
+    // Only source of randomness in the simulation, so a seed plus the same inputs replays the same game
+    rng: StdRng
+}
+
+impl GameState {
+    pub fn New(mapPath: &str, seed: u64, verticalLook: bool) -> Result<Self, LoadError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (map, enemies): (Map, Vec<Enemy>) = Map::Load(mapPath, &mut rng)?;
+        let player = Player::New(map.playerStart.location, map.playerStart.viewDir);
+
+        Ok(Self {
+            player,
+            map,
+            enemies,
+            verticalLook,
+            ticks: 0,
+            rng
+        })
+    }
+
+    pub fn Step(&mut self, inputs: &TickInputs) {
+        self.UpdateEnemies();
+        self.player.Update(inputs, self.verticalLook, &mut self.map, &mut self.enemies, &PLAYER_SPEEDS);
+        let occupiedTiles: Vec<iPoint2> = self.enemies.iter()
+            .filter(|e| e.currState != EnemyState::DEAD)
+            .map(|e| e.tile)
+            .chain([iPoint2::from(self.player.location)])
+            .collect();
+        self.map.UpdateDoors(DOOR_MOVE_INCR, DOOR_TIMER_INCR, &occupiedTiles);
+        self.ticks += 1;
+    }
+
+    fn UpdateEnemies(&mut self) {
+        // Wipe all enemy sprites from tiles
+        for e in self.enemies.iter() {
+            match self.map.GetMutTile(e.tile) {
+                Tile::OBJECT(object) => {
+                    object.enemySprites.clear();
+                },
+                Tile::EMPTY(emptyTile) => {
+                    emptyTile.enemySprites.clear();
+                },
+                Tile::DOOR(door) => {
+                    door.enemySprites.clear();
+                },
+                Tile::NONE => panic!(),
+                _ => {}
+            }
+        }
+
+        // For each enemy...
+        for e in self.enemies.iter_mut() {
+
+            e.Update(&mut self.map, &mut self.player, &mut self.rng);
+
+            // Calculate and inject sprites into appropriate tiles
+            let tileCoord: iPoint2 = e.location.into();
+            let sprite = e.CalculateSprite(self.player.viewDir);
+            match self.map.GetMutTile(tileCoord) {
+                Tile::OBJECT(object) => {
+                    object.enemySprites.push(sprite);
+                },
+                Tile::EMPTY(emptyTile) => {
+                    emptyTile.enemySprites.push(sprite);
+                },
+                Tile::DOOR(door) => {
+                    door.enemySprites.push(sprite);
+                },
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::Path;
+use ndarray::Array2;
+use crate::load_error::{CellOrigin, LoadError};
+use crate::map_file::{ActorCode, EnemyRoutine, Facing, LayerValue, MapCell, MapFile, MapInfo, WallCode};
+use crate::multimedia::{TextureType, TEXTURE_SHEET_SPECS};
+
+/*
+=========================================================
+    Original game level files
+
+    A level of the original game lives in two files: MAPHEAD.<ext> holds the
+    RLEW tag and the offset of each level's header in GAMEMAPS.<ext>, and
+    that header points at three planes, each Carmack compressed on top of
+    RLEW compression. Plane 0 holds walls, doors and floor area numbers,
+    plane 1 the player start, enemies and static objects ; plane 2 is unused.
+
+    Levels are picked by appending `:<level>` to the GAMEMAPS path, counting
+    from 1 (e.g. `GAMEMAPS.WL1:1` is E1M1) ; MAPHEAD is read from next to it.
+    Rows are stored north first, same as the layers of a `.wmap` file ; errors
+    point at `<level path>:<row>:<column>`, showing the cell as its plane 0 and
+    plane 1 numbers, e.g. `75/0`.
+=========================================================
+*/
+
+const MAX_LEVELS: usize = 100;
+const CARMACK_NEAR_TAG: u16 = 0xA7;
+const CARMACK_FAR_TAG: u16 = 0xA8;
+
+/*
+=========================================================
+    Tile number mapping
+=========================================================
+
+    Plane 0
+        1-63      wall ; tile t uses the lit/unlit wall texture pair starting at 2t-1,
+                  the sheet being in the original's VSWAP order. Tiles whose pair is past
+                  the end of the wall sheet (56 on, with 110 textures) are rejected
+        90-101    door ; locked and elevator doors open like any other
+        106-143   floor ; 106 marks ambush spots, the rest are area numbers
+
+    Plane 1
+        19-22     player start facing N, E, S, W
+        23-70     static object ; object texture (code-20), the sheet being in VSWAP sprite order
+                  (the first two sprites are the demo and death cam ones)
+        108-115   guard, standing (108-111) then patrolling (112-115), 4 facings each
+        116-123   officer
+        126-133   SS
+        144-151, 152-159, 162-169   guard, officer, SS added on medium difficulty
+        180-187, 188-195, 198-205   guard, officer, SS added on hard difficulty
+
+    Every difficulty's enemies are loaded, and enemies pick their own facing like they do on
+    other maps ; patrols wander rather than follow the path arrows. Anything else in plane 1
+    (dogs, mutants, bosses, dead guards, path arrows, pushwalls) isn't supported by the engine
+    yet and is left out.
+*/
+
+fn TranslateWallTile(tile: u16) -> Result<Option<WallCode>, String> {
+    let numWallTextures = TEXTURE_SHEET_SPECS.iter().find(|spec| spec.textureType == TextureType::WALL).unwrap().numTextures;
+    match tile {
+        1..=63 if 2*tile as i32 <= numWallTextures => Ok(Some(WallCode::WALL(2*tile as i32 - 1))),
+        1..=63 => Err(format!("wall tile {} needs wall textures {}-{} ; the wall sheet has {}", tile, 2*tile - 1, 2*tile, numWallTextures)),
+        90..=101 => Ok(Some(WallCode::DOOR)),
+        106..=143 => Ok(None),
+        _ => Err(format!("unknown plane 0 tile {} ; expected a wall (1-63), door (90-101) or floor (106-143)", tile))
+    }
+}
+
+enum PlaneObject {
+    ACTOR(ActorCode),
+    OBJECT(i32),
+    NONE
+}
+
+fn TranslateObjectCode(code: u16) -> PlaneObject {
+    match code {
+        19 => PlaneObject::ACTOR(ActorCode::PLAYER(Facing::NORTH)),
+        20 => PlaneObject::ACTOR(ActorCode::PLAYER(Facing::EAST)),
+        21 => PlaneObject::ACTOR(ActorCode::PLAYER(Facing::SOUTH)),
+        22 => PlaneObject::ACTOR(ActorCode::PLAYER(Facing::WEST)),
+        23..=70 => PlaneObject::OBJECT(code as i32 - 20),
+        108..=115 | 144..=151 | 180..=187 => PlaneObject::ACTOR(ActorCode::ENEMY("GU".to_string(), EnemyRoutineOf(code, 108))),
+        116..=123 | 152..=159 | 188..=195 => PlaneObject::ACTOR(ActorCode::ENEMY("OF".to_string(), EnemyRoutineOf(code, 116))),
+        126..=133 | 162..=169 | 198..=205 => PlaneObject::ACTOR(ActorCode::ENEMY("SS".to_string(), EnemyRoutineOf(code, 126))),
+        _ => PlaneObject::NONE
+    }
+}
+
+// Each difficulty's block of 8 codes starts 36 after the previous one's
+fn EnemyRoutineOf(code: u16, firstCode: u16) -> EnemyRoutine {
+    if (code - firstCode) % 36 < 4 { EnemyRoutine::STAND } else { EnemyRoutine::PATROL }
+}
+
+/*
+=========================================================
+    Loading
+=========================================================
+*/
+
+// `<dir>/GAMEMAPS.<ext>[:<level>]` ; None for any other path. The level defaults to 1
+pub fn ParseGameMapsPath(path: &str) -> Option<(&str, usize)> {
+    let (filePath, level) = match path.rsplit_once(':') {
+        Some((filePath, level)) if !level.is_empty() && level.chars().all(|c| c.is_ascii_digit()) => (filePath, level.parse().ok()?),
+        _ => (path, 1)
+    };
+    let fileName = Path::new(filePath).file_name()?.to_str()?;
+    if fileName.to_ascii_uppercase().starts_with("GAMEMAPS") {
+        Some((filePath, level))
+    } else {
+        None
+    }
+}
+
+pub fn ImportGameMaps(gamemapsPath: &str, level: usize) -> Result<MapFile, LoadError> {
+    let levelPath = format!("{}:{}", gamemapsPath, level);
+    let MapError = |path: &str, message: String| LoadError::MAP { path: path.to_string(), message };
+
+    // MAPHEAD sits next to GAMEMAPS with the same extension, and the same case
+    let gamemapsName = Path::new(gamemapsPath).file_name().unwrap().to_str().unwrap();
+    let mapheadName = if gamemapsName.starts_with("gamemaps") { "maphead" } else { "MAPHEAD" }.to_string() + &gamemapsName[8..];
+    let mapheadPath = Path::new(gamemapsPath).with_file_name(mapheadName).to_string_lossy().to_string();
+    let maphead = fs::read(&mapheadPath).map_err(|error| LoadError::IO { path: mapheadPath.clone(), error })?;
+    let gamemaps = fs::read(gamemapsPath).map_err(|error| LoadError::IO { path: gamemapsPath.to_string(), error })?;
+
+    let maphead = Bytes { data: &maphead, path: &mapheadPath };
+    let gamemaps = Bytes { data: &gamemaps, path: &levelPath };
+
+    let rlewTag = maphead.U16(0)?;
+    let numLevels = (0..MAX_LEVELS).take_while(|&i| maphead.U32(2 + 4*i).is_ok_and(|offset| offset != 0 && offset != u32::MAX)).count();
+    if level < 1 || level > numLevels {
+        return Err(MapError(&levelPath, format!("no such level ; {} has levels 1-{}", gamemapsPath, numLevels)));
+    }
+    let headerOffset = maphead.U32(2 + 4*(level-1))? as usize;
+
+    // Level header: three plane offsets, three compressed plane lengths, the size and a 16 byte name
+    let planeOffsets: Vec<usize> = (0..2).map(|i| gamemaps.U32(headerOffset + 4*i).map(|offset| offset as usize)).collect::<Result<_, _>>()?;
+    let planeLengths: Vec<usize> = (0..2).map(|i| gamemaps.U16(headerOffset + 12 + 2*i).map(|length| length as usize)).collect::<Result<_, _>>()?;
+    let width = gamemaps.U16(headerOffset + 18)? as usize;
+    let height = gamemaps.U16(headerOffset + 20)? as usize;
+    let nameBytes = gamemaps.Slice(headerOffset + 22, 16)?;
+    let name = String::from_utf8_lossy(nameBytes.split(|&b| b == 0).next().unwrap()).trim().to_string();
+
+    let mut planes: Vec<Vec<u16>> = Vec::new();
+    for plane in 0..2 {
+        let compressed = gamemaps.Slice(planeOffsets[plane], planeLengths[plane])?;
+        let words = CarmackExpand(compressed).map_err(|message| MapError(&levelPath, format!("plane {}: {}", plane, message)))?;
+        let words = RLEWExpand(&words, rlewTag, width*height).map_err(|message| MapError(&levelPath, format!("plane {}: {}", plane, message)))?;
+        planes.push(words);
+    }
+
+    // Rows are stored north first ; the map's y axis points north
+    let mut cells: Array2<MapCell> = Array2::default((height, width));
+    for row in 0..height {
+        for x in 0..width {
+            let (tile, code) = (planes[0][row*width + x], planes[1][row*width + x]);
+            let origin = CellOrigin { text: format!("{}/{}", tile, code), line: row as u64 + 1, column: x + 1 };
+            let cell = &mut cells[(height - 1 - row, x)];
+
+            cell.wall = TranslateWallTile(tile).map_err(|message| origin.Error(&levelPath, message))?
+                .and_then(|wall| LayerValue::New(wall, &origin));
+            match TranslateObjectCode(code) {
+                PlaneObject::ACTOR(actor) => cell.actor = LayerValue::New(actor, &origin),
+                PlaneObject::OBJECT(ID) => cell.object = LayerValue::New(ID, &origin),
+                PlaneObject::NONE => {}
+            }
+            cell.origin = origin;
+        }
+    }
+
+    let info = MapInfo { name, width: width as i32, height: height as i32, parSeconds: None };
+    Ok(MapFile { path: levelPath, info, settings: Default::default(), cells })
+}
+
+// Little endian reads that report a truncated file instead of panicking
+struct Bytes<'a> {
+    data: &'a [u8],
+    path: &'a str
+}
+
+impl<'a> Bytes<'a> {
+    fn Slice(&self, offset: usize, length: usize) -> Result<&'a [u8], LoadError> {
+        self.data.get(offset..offset+length).ok_or_else(|| LoadError::MAP {
+            path: self.path.to_string(),
+            message: format!("file is truncated ; needed {} byte(s) at offset {}, it is {} long", length, offset, self.data.len())
+        })
+    }
+
+    fn U16(&self, offset: usize) -> Result<u16, LoadError> {
+        self.Slice(offset, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn U32(&self, offset: usize) -> Result<u32, LoadError> {
+        self.Slice(offset, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/*
+=========================================================
+    Decompression
+=========================================================
+*/
+
+// Carmack compression works on words ; a word whose high byte is a tag copies `count` earlier words, from `offset`
+// words back (near tag, byte offset) or from `offset` words into the output (far tag, word offset). A count of 0
+// escapes a literal word with that high byte, its low byte following. The first word is the expanded size in bytes
+pub fn CarmackExpand(data: &[u8]) -> Result<Vec<u16>, String> {
+    let truncated = || "compressed data ends early".to_string();
+    let mut pos = 0;
+    let mut ReadByte = || { pos += 1; data.get(pos-1).copied().ok_or_else(truncated) };
+
+    let expandedBytes = u16::from_le_bytes([ReadByte()?, ReadByte()?]) as usize;
+    let mut out: Vec<u16> = Vec::with_capacity(expandedBytes / 2);
+    while out.len() < expandedBytes / 2 {
+        let word = u16::from_le_bytes([ReadByte()?, ReadByte()?]);
+        let (tag, count) = (word >> 8, (word & 0xFF) as usize);
+
+        if (tag == CARMACK_NEAR_TAG || tag == CARMACK_FAR_TAG) && count == 0 {
+            out.push((tag << 8) | ReadByte()? as u16);
+            continue;
+        }
+        let start = match tag {
+            CARMACK_NEAR_TAG => {
+                let offset = ReadByte()? as usize;
+                out.len().checked_sub(offset).filter(|_| offset > 0).ok_or("near copy reaches before the start of the data")?
+            },
+            CARMACK_FAR_TAG => u16::from_le_bytes([ReadByte()?, ReadByte()?]) as usize,
+            _ => {
+                out.push(word);
+                continue;
+            }
+        };
+        if start >= out.len() {
+            return Err("far copy starts past the data expanded so far".to_string());
+        }
+        // Copies may overlap what they're writing, so go word by word
+        for i in 0..count {
+            out.push(out[start + i]);
+        }
+    }
+
+    out.truncate(expandedBytes / 2);
+    Ok(out)
+}
+
+// RLEW replaces runs with `tag, count, value` ; the first word is the expanded size in bytes
+pub fn RLEWExpand(words: &[u16], tag: u16, expectedWords: usize) -> Result<Vec<u16>, String> {
+    let expandedBytes = *words.first().ok_or("plane is empty")? as usize;
+    if expandedBytes != expectedWords*2 {
+        return Err(format!("plane expands to {} byte(s) ; the map's size needs {}", expandedBytes, expectedWords*2));
+    }
+
+    let mut out: Vec<u16> = Vec::with_capacity(expectedWords);
+    let mut input = words[1..].iter().copied();
+    while out.len() < expectedWords {
+        let word = input.next().ok_or("compressed data ends early")?;
+        if word == tag {
+            let (count, value) = (input.next(), input.next());
+            let (count, value) = count.zip(value).ok_or("compressed data ends early")?;
+            out.extend(std::iter::repeat_n(value, count as usize));
+        } else {
+            out.push(word);
+        }
+    }
+
+    out.truncate(expectedWords);
+    Ok(out)
+}
@@ -0,0 +1,267 @@
+/*
+=========================================================
+    Golden-image regression tests for the raycaster
+
+    Each scene places the player at a fixed spot in a map, renders a frame
+    through the software Framebuffer and compares it against tests/golden/<name>.bmp.
+    Run with UPDATE_GOLDEN=1 to (re)generate the reference images after an
+    intentional rendering change ; on failure the actual frame is written to
+    target/golden/<name>.actual.bmp for inspection.
+=========================================================
+*/
+
+use std::env;
+use std::fs;
+use rand::{rngs::StdRng, SeedableRng};
+use crate::framebuffer::Framebuffer;
+use crate::map::Map;
+use crate::player::Player;
+use crate::raycaster::{Raycaster, RenderResolution};
+use crate::tiles::{Tile, DoorStatus};
+use crate::utils::bmp::{Bitmap, LoadBMP, SaveBMP};
+use crate::utils::vec2d::{iPoint2, Point2, Vec2};
+
+const GOLDEN_SIZE: (usize, usize) = (320, 180);
+const GOLDEN_FOV: f64 = 90.0;
+const GOLDEN_MAP: &str = "assets/map.wmap";
+const GOLDEN_DIR: &str = "tests/golden";
+const ACTUAL_DIR: &str = "target/golden";
+
+// Max per-channel difference for two pixels to count as equal, and max fraction of pixels allowed to differ
+const CHANNEL_TOLERANCE: u8 = 8;
+const MAX_MISMATCH_FRACTION: f64 = 0.002;
+
+struct GoldenScene {
+    name: &'static str,
+    windowSize: (usize, usize),
+    renderResolution: RenderResolution,
+    mapPath: &'static str,
+    location: (f64, f64),
+    viewDir: (f64, f64),
+    pitch: f64,
+    doorPositions: &'static [((i32, i32), f64)]
+}
+
+fn RenderScene(scene: &GoldenScene) -> Bitmap {
+    let mut player = Player::New(Point2::New(scene.location.0, scene.location.1), Vec2::New(scene.viewDir.0, scene.viewDir.1));
+    player.pitch = scene.pitch;
+
+    let (mut map, _) = Map::Load(scene.mapPath, &mut StdRng::seed_from_u64(0)).unwrap();
+    for &((x, y), position) in scene.doorPositions {
+        if let Tile::DOOR(door) = map.GetMutTile(iPoint2::New(x, y)) {
+            door.position = position;
+            door.status = if position == 0.0 { DoorStatus::OPEN } else { DoorStatus::OPENING };
+        } else {
+            panic!("golden scene '{}': no door at ({}, {})", scene.name, x, y);
+        }
+    }
+
+    let (width, height) = scene.windowSize;
+    let mut raycaster = Raycaster::New(width, height, GOLDEN_FOV, &map);
+    raycaster.SetRenderResolution(scene.renderResolution);
+    let mut framebuffer = Framebuffer::New(width, height);
+    raycaster.RenderFrame(&mut framebuffer, &player.GetPose(), &player, &map);
+
+    framebuffer.frame
+}
+
+fn CountMismatchedPixels(actual: &Bitmap, expected: &Bitmap) -> usize {
+    actual.pixels.iter().zip(expected.pixels.iter())
+        .filter(|(a, e)| (0..3).any(|c| a[c].abs_diff(e[c]) > CHANNEL_TOLERANCE))
+        .count()
+}
+
+fn CheckScene(scene: GoldenScene) {
+    let actual = RenderScene(&scene);
+    let goldenPath = format!("{}/{}.bmp", GOLDEN_DIR, scene.name);
+
+    if env::var("UPDATE_GOLDEN").is_ok() {
+        fs::create_dir_all(GOLDEN_DIR).unwrap();
+        SaveBMP(&goldenPath, &actual).unwrap();
+        return;
+    }
+
+    let expected = LoadBMP(&goldenPath)
+        .unwrap_or_else(|e| panic!("missing reference image {} ({}) ; run with UPDATE_GOLDEN=1 to create it", goldenPath, e));
+    assert_eq!((actual.width, actual.height), (expected.width, expected.height), "golden scene '{}': size mismatch", scene.name);
+
+    let mismatched = CountMismatchedPixels(&actual, &expected);
+    let allowed = (MAX_MISMATCH_FRACTION * actual.pixels.len() as f64) as usize;
+    if mismatched > allowed {
+        fs::create_dir_all(ACTUAL_DIR).unwrap();
+        let actualPath = format!("{}/{}.actual.bmp", ACTUAL_DIR, scene.name);
+        SaveBMP(&actualPath, &actual).unwrap();
+        panic!("golden scene '{}': {} pixels differ (allowed {}) ; actual frame written to {}", scene.name, mismatched, allowed, actualPath);
+    }
+}
+
+#[test]
+fn spawn_view_walls_and_objects() {
+    CheckScene(GoldenScene {
+        name: "spawn_view",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: GOLDEN_MAP,
+        location: (22.5, 2.5),
+        viewDir: (-0.46290, 0.88640),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn closed_door_gate() {
+    CheckScene(GoldenScene {
+        name: "closed_door",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: GOLDEN_MAP,
+        location: (12.5, 2.5),
+        viewDir: (-1.0, 0.1),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn half_open_door_gate() {
+    CheckScene(GoldenScene {
+        name: "half_open_door",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: GOLDEN_MAP,
+        location: (12.5, 2.5),
+        viewDir: (-1.0, 0.1),
+        pitch: 0.0,
+        doorPositions: &[((10, 2), 0.5)]
+    });
+}
+
+#[test]
+fn open_door_gate_sidewalls() {
+    CheckScene(GoldenScene {
+        name: "open_door_sidewalls",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: GOLDEN_MAP,
+        location: (11.5, 2.3),
+        viewDir: (-1.0, 0.05),
+        pitch: 0.0,
+        doorPositions: &[((10, 2), 0.0)]
+    });
+}
+
+#[test]
+fn enemy_sprites() {
+    CheckScene(GoldenScene {
+        name: "enemy_sprites",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: GOLDEN_MAP,
+        location: (5.5, 7.5),
+        viewDir: (0.13, -1.0),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn textured_floor_and_ceiling() {
+    CheckScene(GoldenScene {
+        name: "textured_flats",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: "tests/maps/textured_flats.csv",
+        location: (2.5, 1.5),
+        viewDir: (0.6, 1.0),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn distance_falloff_and_fog() {
+    CheckScene(GoldenScene {
+        name: "dark_fog",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: "tests/maps/dark_fog.csv",
+        location: (2.5, 1.5),
+        viewDir: (0.05, 1.0),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn view_pitch_shears_walls_sprites_and_flats() {
+    CheckScene(GoldenScene {
+        name: "view_pitch_down",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: "tests/maps/textured_flats.csv",
+        location: (2.5, 1.5),
+        viewDir: (0.6, 1.0),
+        pitch: -0.25,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn sprites_depth_tested_through_open_door() {
+    CheckScene(GoldenScene {
+        name: "sprite_occlusion",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: "tests/maps/sprite_occlusion.csv",
+        location: (8.6, 3.3),
+        viewDir: (-1.0, 0.1),
+        pitch: 0.0,
+        doorPositions: &[((4, 3), 0.0)]
+    });
+}
+
+#[test]
+fn non_widescreen_window_keeps_proportions() {
+    CheckScene(GoldenScene {
+        name: "spawn_view_4_3",
+        windowSize: (240, 180),
+        renderResolution: RenderResolution::NATIVE,
+        mapPath: GOLDEN_MAP,
+        location: (22.5, 2.5),
+        viewDir: (-0.46290, 0.88640),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn low_internal_resolution_upscaled_to_window() {
+    CheckScene(GoldenScene {
+        name: "spawn_view_160x100",
+        windowSize: GOLDEN_SIZE,
+        renderResolution: RenderResolution::FIXED(160, 100),
+        mapPath: GOLDEN_MAP,
+        location: (22.5, 2.5),
+        viewDir: (-0.46290, 0.88640),
+        pitch: 0.0,
+        doorPositions: &[]
+    });
+}
+
+#[test]
+fn resize_matches_fresh_raycaster() {
+    let (map, _) = Map::Load(GOLDEN_MAP, &mut StdRng::seed_from_u64(0)).unwrap();
+    let player = Player::New(map.playerStart.location, map.playerStart.viewDir);
+
+    let mut resized = Raycaster::New(GOLDEN_SIZE.0, GOLDEN_SIZE.1, GOLDEN_FOV, &map);
+    resized.Resize(240, 180);
+    let mut resizedFrame = Framebuffer::New(240, 180);
+    resized.RenderFrame(&mut resizedFrame, &player.GetPose(), &player, &map);
+
+    let mut fresh = Raycaster::New(240, 180, GOLDEN_FOV, &map);
+    let mut freshFrame = Framebuffer::New(240, 180);
+    fresh.RenderFrame(&mut freshFrame, &player.GetPose(), &player, &map);
+
+    assert!(resizedFrame.frame.pixels == freshFrame.frame.pixels, "resized raycaster renders differently from a fresh one");
+}
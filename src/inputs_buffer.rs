@@ -3,32 +3,12 @@ use sdl2::keyboard::Scancode;
 use sdl2::{
     keyboard::Keycode,
     event::Event::*,
+    event::WindowEvent,
 };
 use sdl2::mouse::MouseButton;
+use crate::game_state::{TickInputs, moveCommand_t};
 
-#[derive(Default, Debug)]
-pub enum lookCommand_t {
-    RIGHT,
-    LEFT,
-
-    #[default]
-    NONE
-}
-
-#[derive(Default, Debug)]
-pub enum moveCommand_t {
-    NORTH,
-    SOUTH,
-    EAST,
-    WEST,
-    NORTH_EAST,
-    NORTH_WEST,
-
-    #[default]
-    NONE
-}
-
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
 pub enum doorCommand_t {
     OPEN,
 
@@ -38,23 +18,32 @@ pub enum doorCommand_t {
 
 #[derive(Default, Debug)]
 pub struct InputsBuffer {
-    pub lookCommand: lookCommand_t,
     pub moveCommand: moveCommand_t,
     pub doorCommand: doorCommand_t,
     pub quit: bool,
 
-    // Mouse related
+    // Window related ; only set for the frame the event arrived in
+    pub toggleFullscreen: bool,
+    pub windowResized: bool,
+    pub cycleRenderResolution: bool,
+    pub toggleProfiler: bool,
+
+    // Mouse related ; motion accumulates across polls until a simulation tick consumes it
     pub fireWeapon: bool,
-    pub mouseAbsXrel: i32,
-    pub prevXrel: i32,
-    pub windowLock: bool
+    pub mouseXrel: i32,
+    pub mouseYrel: i32,
+    pub windowLock: bool,
+    pub verticalLook: bool
 }
 
 impl InputsBuffer {
     pub fn Update(&mut self, sdlContext: &mut sdl2::Sdl, sdlEventPump: &mut EventPump) {
         
         /* Escape key and mouse */
-        let mut currXrel = 0;
+        self.toggleFullscreen = false;
+        self.windowResized = false;
+        self.cycleRenderResolution = false;
+        self.toggleProfiler = false;
         for event in sdlEventPump.poll_iter() {
             match event {
                 Quit {..} | KeyDown { keycode: Some(Keycode::Escape), .. } => {
@@ -65,14 +54,23 @@ impl InputsBuffer {
                     sdlContext.mouse().set_relative_mouse_mode(!self.windowLock);
                     self.windowLock = !self.windowLock;
                 },
-                MouseMotion { xrel, ..} => {
-                    currXrel = xrel;
-                    if currXrel > 0 {
-                        self.lookCommand = lookCommand_t::RIGHT;
-                    } else if currXrel < 0 {
-                        self.lookCommand = lookCommand_t::LEFT;
+                KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+                    self.toggleFullscreen = true;
+                },
+                KeyDown { keycode: Some(Keycode::F2), repeat: false, .. } => {
+                    self.cycleRenderResolution = true;
+                },
+                KeyDown { keycode: Some(Keycode::F3), repeat: false, .. } => {
+                    self.toggleProfiler = true;
+                },
+                Window { win_event: WindowEvent::SizeChanged(..), .. } => {
+                    self.windowResized = true;
+                },
+                MouseMotion { xrel, yrel, ..} => {
+                    if self.verticalLook {
+                        self.mouseYrel += yrel;
                     }
-                    self.mouseAbsXrel = currXrel.abs();
+                    self.mouseXrel += xrel;
                 },
                 MouseButtonDown { mouse_btn, .. } => {
                     if mouse_btn == MouseButton::Left {
@@ -88,13 +86,6 @@ impl InputsBuffer {
             }
         }
 
-        // Detect when mouse is not moving anymore
-        if currXrel == self.prevXrel && sdlEventPump.relative_mouse_state().x() == 0 {
-            self.mouseAbsXrel = 0;
-            self.lookCommand = lookCommand_t::NONE;
-        }
-        self.prevXrel = currXrel;
-
         /* Keyboard buttons */
         let ks = sdlEventPump.keyboard_state();
 
@@ -118,4 +109,21 @@ impl InputsBuffer {
             doorCommand_t::NONE
         };
     }
-}
\ No newline at end of file
+
+    // What the next simulation tick sees of the current inputs
+    pub fn TickInputs(&self) -> TickInputs {
+        TickInputs {
+            moveCommand: self.moveCommand,
+            mouseXrel: self.mouseXrel,
+            mouseYrel: self.mouseYrel,
+            openDoor: self.doorCommand == doorCommand_t::OPEN,
+            fireWeapon: self.fireWeapon
+        }
+    }
+
+    // Called after each simulation tick, so mouse motion turns the view once no matter how many ticks a frame runs
+    pub fn ConsumeMouseMotion(&mut self) {
+        self.mouseXrel = 0;
+        self.mouseYrel = 0;
+    }
+}
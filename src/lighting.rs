@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use sdl2::pixels::Color;
+
+/*
+=========================================================
+    Shade struct definition
+=========================================================
+*/
+
+// How a texel gets lit on screen: final = texel*brightness*(1 - fogAmount) + fogColor*fogAmount
+#[derive(Copy, Clone, PartialEq)]
+pub struct Shade {
+    pub brightness: f64,
+    pub fogColor: Color,
+    pub fogAmount: f64
+}
+
+impl Shade {
+    pub const FULL_BRIGHT: Shade = Shade { brightness: 1.0, fogColor: Color::RGBA(0, 0, 0, 255), fogAmount: 0.0 };
+
+    pub fn IsFullBright(&self) -> bool {
+        self.brightness >= 1.0 && self.fogAmount <= 0.0
+    }
+
+    pub fn Apply(&self, texel: [u8; 4]) -> [u8; 4] {
+        if self.IsFullBright() {
+            return texel;
+        }
+        let lit = self.brightness*(1.0 - self.fogAmount);
+        let mix = |c: u8, fog: u8| (c as f64*lit + fog as f64*self.fogAmount).round().clamp(0.0, 255.0) as u8;
+        [mix(texel[0], self.fogColor.r), mix(texel[1], self.fogColor.g), mix(texel[2], self.fogColor.b), texel[3]]
+    }
+}
+
+/*
+=========================================================
+    Lighting struct definition
+=========================================================
+*/
+
+// Per-map distance lighting. Brightness falls off as ambient/(1 + attenuation*dist) ; fog (if any)
+// ramps linearly from nothing at fogStart to fully fogged at fogEnd. Distances are perpendicular
+// to the view plane, so walls, sprites and flats at the same depth get the same shade.
+#[derive(Copy, Clone)]
+pub struct Lighting {
+    pub ambient: f64,
+    pub attenuation: f64,
+    pub fog: Option<Fog>
+}
+
+#[derive(Copy, Clone)]
+pub struct Fog {
+    pub color: Color,
+    pub start: f64,
+    pub end: f64
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            ambient: 1.0,
+            attenuation: 0.0,
+            fog: None
+        }
+    }
+}
+
+impl Lighting {
+    // Reads the `light.*` and `fog.*` keys of a map header, falling back to full brightness and no fog
+    pub fn FromMapHeader(header: &HashMap<String, String>) -> Result<Self, String> {
+        let mut lighting = Lighting::default();
+
+        if let Some(value) = header.get("light.ambient") {
+            lighting.ambient = ParseNumber("light.ambient", value)?;
+        }
+        if let Some(value) = header.get("light.attenuation") {
+            lighting.attenuation = ParseNumber("light.attenuation", value)?;
+        }
+        if let Some(value) = header.get("fog.color") {
+            let channels: Vec<&str> = value.split_whitespace().collect();
+            if channels.len() != 3 {
+                return Err(format!("fog.color: expected `R G B`, got `{}`", value));
+            }
+            let mut rgb = [0u8; 3];
+            for (i, channel) in channels.iter().enumerate() {
+                rgb[i] = channel.parse::<u8>().map_err(|_| format!("fog.color: `{}` is not a 0-255 channel value", channel))?;
+            }
+            let start = match header.get("fog.start") { Some(v) => ParseNumber("fog.start", v)?, None => 0.0 };
+            let end = match header.get("fog.end") { Some(v) => ParseNumber("fog.end", v)?, None => 16.0 };
+            lighting.fog = Some(Fog {
+                color: Color::RGBA(rgb[0], rgb[1], rgb[2], 255),
+                start,
+                end
+            });
+        }
+
+        Ok(lighting)
+    }
+
+    // True when every distance gets Shade::FULL_BRIGHT, so renderers can skip shading altogether
+    pub fn IsUniform(&self) -> bool {
+        self.ambient >= 1.0 && self.attenuation == 0.0 && self.fog.is_none()
+    }
+
+    pub fn GetShade(&self, dist: f64) -> Shade {
+        let dist = dist.max(0.0);
+        let brightness = (self.ambient / (1.0 + self.attenuation*dist)).clamp(0.0, 1.0);
+
+        match self.fog {
+            Some(fog) => {
+                let fogAmount = if fog.end > fog.start {
+                    ((dist - fog.start) / (fog.end - fog.start)).clamp(0.0, 1.0)
+                } else if dist >= fog.start { 1.0 } else { 0.0 };
+                Shade { brightness, fogColor: fog.color, fogAmount }
+            },
+            None => Shade { brightness, ..Shade::FULL_BRIGHT }
+        }
+    }
+}
+
+fn ParseNumber(key: &str, value: &str) -> Result<f64, String> {
+    value.trim().parse::<f64>().map_err(|_| format!("{}: `{}` is not a number", key, value))
+}
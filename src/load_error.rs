@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/*
+=========================================================
+    LoadError definition
+=========================================================
+*/
+
+// Everything that can go wrong reading maps and assets from disk ; each variant names the file, and map cell
+// errors also say where in it, so the message points straight at the mistake
+#[derive(Debug)]
+pub enum LoadError {
+    IO {
+        path: String,
+        error: io::Error
+    },
+    CSV {
+        path: String,
+        error: csv::Error
+    },
+    IMAGE {
+        path: String,
+        message: String
+    },
+    HEADER {
+        path: String,
+        message: String
+    },
+    // A malformed line in a layered map file, outside of any one cell
+    SYNTAX {
+        path: String,
+        line: u64,
+        message: String
+    },
+    // A problem with the map as a whole rather than any one cell
+    MAP {
+        path: String,
+        message: String
+    },
+    // line and column are 1-based, as shown by text editors and spreadsheets
+    CELL {
+        path: String,
+        line: u64,
+        column: usize,
+        cell: String,
+        message: String
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::IO { path, error } => write!(f, "{}: {}", path, error),
+            LoadError::CSV { path, error } => write!(f, "{}: {}", path, error),
+            LoadError::IMAGE { path, message } => write!(f, "{}: {}", path, message),
+            LoadError::HEADER { path, message } => write!(f, "{}: header: {}", path, message),
+            LoadError::SYNTAX { path, line, message } => write!(f, "{}:{}: {}", path, line, message),
+            LoadError::MAP { path, message } => write!(f, "{}: {}", path, message),
+            LoadError::CELL { path, line, column, cell, message } => write!(f, "{}:{}:{}: cell `{}`: {}", path, line, column, cell, message)
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::IO { error, .. } => Some(error),
+            LoadError::CSV { error, .. } => Some(error),
+            _ => None
+        }
+    }
+}
+
+/*
+=========================================================
+    CellOrigin struct definition
+=========================================================
+*/
+
+// Where a map cell came from, for error messages ; line and column are 1-based
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct CellOrigin {
+    pub text: String,
+    pub line: u64,
+    pub column: usize
+}
+
+impl CellOrigin {
+    // A CELL error pointing at this cell
+    pub fn Error(&self, path: &str, message: String) -> LoadError {
+        LoadError::CELL {
+            path: path.to_string(),
+            line: self.line,
+            column: self.column,
+            cell: self.text.clone(),
+            message
+        }
+    }
+}
@@ -1,18 +1,56 @@
 #![allow(non_snake_case)]
 
 use std::env;
+use std::error::Error;
+use std::process::ExitCode;
 
 mod utils;
 mod multimedia;
 mod inputs_buffer;
 mod player;
 mod map;
+mod map_file;
+mod gamemaps;
 mod tiles;
 mod engine;
+mod game_state;
+mod renderer;
+mod raycaster;
+mod framebuffer;
+mod lighting;
+mod profiler;
+mod demo;
+mod load_error;
+mod map_validator;
+mod vswap;
+mod pathfinding;
+mod areas;
+
+#[cfg(test)]
+mod golden_tests;
+#[cfg(test)]
+mod demo_tests;
+#[cfg(test)]
+mod map_tests;
+#[cfg(test)]
+mod vswap_tests;
+#[cfg(test)]
+mod enemy_tests;
+#[cfg(test)]
+mod pathfinding_tests;
+#[cfg(test)]
+mod areas_tests;
+#[cfg(test)]
+mod enemy_types_tests;
 mod animation;
 mod enemy;
+mod enemy_types;
 
-use engine::GameEngine;
+use engine::{GameEngine, EngineSettings, RenderHeadlessFrame, PlayDemoHeadless};
+use map_file::MapFile;
+use map_validator::ValidateMap;
+use multimedia::TextureSource;
+use raycaster::RenderResolution;
 
 fn set_resources_path() {
     if let Ok(exec_path) = env::current_exe() {
@@ -29,8 +67,99 @@ fn set_resources_path() {
     }
 }
 
-fn main() {
+fn main() -> ExitCode {
     set_resources_path();
-    let mut gameEngine = GameEngine::Init(1280, 720, 90.0, "assets/map.csv");
+
+    // Map and asset problems come back here and are printed as `file:line:column: ...` diagnostics
+    match Run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn Run() -> Result<(), Box<dyn Error>> {
+    // `--render-frame <out.bmp>` renders a single frame in software and exits, for machines without a display
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 && args[1] == "--render-frame" {
+        return RenderHeadlessFrame(1280, 720, 90.0, "assets/map.wmap", &args[2]);
+    }
+
+    // `--convert-map <in> <out.wmap>` rewrites a CSV map or a level of the original game in the layered format
+    if args.len() == 4 && args[1] == "--convert-map" {
+        MapFile::Load(&args[2])?.Save(&args[3])?;
+        println!("{} -> {}", args[2], args[3]);
+        return Ok(());
+    }
+
+    // `--validate <map>` checks a map without opening a window ; exits non-zero if anything is wrong, for CI
+    if let Some(mapPath) = OptionValue(&args, "--validate")? {
+        let problems = ValidateMap(mapPath)?;
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if !problems.is_empty() {
+            return Err(format!("{}: {} problem(s) found", mapPath, problems.len()).into());
+        }
+        println!("{}: ok", mapPath);
+        return Ok(());
+    }
+
+    // `--play-demo <demo.csv> --headless` replays a recorded demo without a window and prints the final state
+    if let Some(demoPath) = OptionValue(&args, "--play-demo")? {
+        if args.iter().any(|arg| arg == "--headless") {
+            let report = PlayDemoHeadless(demoPath)?;
+            print!("{}", report);
+            return Ok(());
+        }
+    }
+
+    let mut settings = EngineSettings::default();
+
+    // `--map <path>` plays another map ; a `.wmap`, a CSV map, or a level of the original game as `GAMEMAPS.WL1:<level>`
+    if let Some(path) = OptionValue(&args, "--map")? {
+        settings.mapPath = path.to_string();
+    }
+
+    // `--vswap <VSWAP.WL6> --palette <file>` takes textures and sprites from the original game's data instead of the BMP sheets
+    if let Some(vswapPath) = OptionValue(&args, "--vswap")? {
+        let palettePath = OptionValue(&args, "--palette")?.ok_or("--vswap needs a --palette ; VSWAP files don't include one")?;
+        settings.textureSource = TextureSource::VSWAP { vswapPath: vswapPath.to_string(), palettePath: palettePath.to_string() };
+    }
+
+    // `--no-vertical-look` keeps the horizon fixed, like the original game
+    settings.verticalLook = !args.iter().any(|arg| arg == "--no-vertical-look");
+
+    // `--render-resolution native|half|quarter|WIDTHxHEIGHT` renders at a lower resolution and upscales to the window
+    if let Some(value) = OptionValue(&args, "--render-resolution")? {
+        settings.renderResolution = RenderResolution::FromArg(value)
+            .ok_or_else(|| format!("invalid --render-resolution `{}` ; expected native, half, quarter or WIDTHxHEIGHT", value))?;
+    }
+
+    // `--profile` starts with the frame timing overlay shown ; `--profile-csv <out.csv>` also records every frame's timings
+    settings.showProfiler = args.iter().any(|arg| arg == "--profile");
+    settings.profileCSVPath = OptionValue(&args, "--profile-csv")?.map(|path| path.to_string());
+
+    // `--seed <n>` makes enemy behaviour reproducible ; the seed in use is printed at startup either way
+    if let Some(value) = OptionValue(&args, "--seed")? {
+        settings.seed = Some(value.parse::<u64>().map_err(|_| format!("invalid --seed `{}` ; expected a non-negative integer", value))?);
+    }
+
+    // `--record-demo <out.csv>` records every tick's inputs ; `--play-demo <demo.csv>` plays one back in the window
+    settings.recordDemoPath = OptionValue(&args, "--record-demo")?.map(|path| path.to_string());
+    settings.playDemoPath = OptionValue(&args, "--play-demo")?.map(|path| path.to_string());
+
+    let mut gameEngine = GameEngine::Init(&settings)?;
     gameEngine.GameLoop();
+    Ok(())
+}
+
+// Value following a `--flag value` style option ; None if the flag isn't given, an error if it's given without one
+fn OptionValue<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => args.get(i + 1).map(|value| Some(value.as_str())).ok_or_else(|| format!("{} needs a value", flag)),
+        None => Ok(None)
+    }
 }
@@ -1,129 +1,178 @@
-use crate::enemy::{Enemy, EnemyType};
+use crate::areas::Areas;
+use crate::enemy::Enemy;
+use crate::enemy_types::EnemyTypes;
 
-use crate::multimedia::TextureType;
-use crate::tiles::{Wall, EmptyTile, Door, Tile, Sprite, TextureHandle, ObjectTile};
-use crate::utils::vec2d::{iPoint2, Point2, RandomUnitVec};
-use crate::utils::csv::ParseCSV;
+use crate::multimedia::{TextureType, TEXTURE_SHEET_SPECS};
+use crate::tiles::{Wall, EmptyTile, Door, DoorStatus, Tile, Sprite, TextureHandle, ObjectTile};
+use crate::utils::vec2d::{iPoint2, Point2, RandomUnitVec, Vec2};
+use crate::map_file::{ActorCode, MapFile, MapInfo, TileFlags, WallCode};
+use crate::lighting::Lighting;
+use crate::utils::dda::RayCursor;
+use crate::utils::ray::Ray;
+use crate::load_error::{CellOrigin, LoadError};
+use rand::Rng;
+
+// Where the `P-<facing>` cell puts the player when the map starts
+#[derive(Copy, Clone)]
+pub struct PlayerStart {
+    pub location: Point2,
+    pub viewDir: Vec2
+}
 
 pub struct Map {
+    pub info: MapInfo,
     pub width: i32,
     pub height: i32,
+    pub lighting: Lighting,
+    pub playerStart: PlayerStart,
     tiles: Vec<Vec<Tile>>,
     doorTileCoords: Vec<iPoint2>,
+    floorTextures: Vec<Vec<Option<TextureHandle>>>,
+    ceilingTextures: Vec<Vec<Option<TextureHandle>>>,
+    flags: Vec<Vec<TileFlags>>,
+    areas: Areas,
 }
 
 impl Map {
-    pub fn LoadFromCSV(csvPath: &str, refreshRate: usize) -> (Self, Vec<Enemy>) {
-        let tileTextureIDs = ParseCSV(csvPath).unwrap();
-        let width = tileTextureIDs.ncols() as i32;
-        let height = tileTextureIDs.nrows() as i32;
+    // rng picks the enemies' starting facings
+    pub fn Load(path: &str, rng: &mut impl Rng) -> Result<(Self, Vec<Enemy>), LoadError> {
+        Self::FromMapFile(&MapFile::Load(path)?, rng)
+    }
+
+    pub fn FromMapFile(mapFile: &MapFile, rng: &mut impl Rng) -> Result<(Self, Vec<Enemy>), LoadError> {
+        let path = mapFile.path.as_str();
+        let lighting = Lighting::FromMapHeader(&mapFile.settings)
+            .map_err(|message| LoadError::HEADER { path: path.to_string(), message })?;
+        let enemyTypes = EnemyTypes::FromMapHeader(&mapFile.settings)?;
+        let width = mapFile.info.width;
+        let height = mapFile.info.height;
         let mut tiles: Vec<Vec<Tile>> = vec![vec![Tile::NONE; height as usize]; width as usize];
+        let mut flags: Vec<Vec<TileFlags>> = vec![vec![TileFlags::default(); height as usize]; width as usize];
         
         let mut doorTileCoords: Vec<iPoint2> = Vec::new();
 
+        // Flats follow the wall lit/unlit pairing: floors use the lit texture ID, ceilings the unlit one (ID+1)
+        let mut floorTextures: Vec<Vec<Option<TextureHandle>>> = vec![vec![None; height as usize]; width as usize];
+        let mut ceilingTextures: Vec<Vec<Option<TextureHandle>>> = vec![vec![None; height as usize]; width as usize];
+
         let mut enemies: Vec<Enemy> = Vec::new();
+        let mut playerStarts: Vec<(&CellOrigin, PlayerStart)> = Vec::new();
 
         for column in 0..width {
             for row in 0..height {
-                let tileCode = tileTextureIDs.get((row as usize, column as usize)).unwrap();
-                let tileTypeCode = tileCode.0.clone();
-                let tileTextureID = tileCode.1;
+                let cell = &mapFile.cells[(row as usize, column as usize)];
+
+                if let Some(floor) = &cell.floor {
+                    CheckTextureID(path, "floor", &floor.origin, TextureType::WALL, floor.value, floor.value)?;
+                    floorTextures[column as usize][row as usize] = Some(TextureHandle::New(TextureType::WALL, floor.value));
+                }
+                if let Some(ceiling) = &cell.ceiling {
+                    CheckTextureID(path, "ceiling", &ceiling.origin, TextureType::WALL, ceiling.value, ceiling.value+1)?;
+                    ceilingTextures[column as usize][row as usize] = Some(TextureHandle::New(TextureType::WALL, ceiling.value+1));
+                }
+                flags[column as usize][row as usize] = cell.flags;
 
                 let spriteLocation = Point2::New(column as f64 + 0.5, row as f64 + 0.5);
                 let spriteTile: iPoint2 = spriteLocation.into();
 
-                tiles[column as usize][row as usize] = match tileTypeCode.as_str() {
-                    "" => Tile::EMPTY(EmptyTile::New(None)),
-                    "W" => Tile::WALL(Wall::New(tileTextureID.unwrap(), tileTextureID.unwrap()+1)),
-                    "D" => {
-                        doorTileCoords.push(iPoint2::New(column, row));
-                        Tile::DOOR(Door::New())
-                    },
-                    "GU" => {
-                        let guard = Sprite {
-                            textureHandle: TextureHandle::New(TextureType::GUARD, 1),
-                            location: spriteLocation
-                        };
-
-                        let mut sprites: Vec<Sprite> = Vec::new();
-                            sprites.push(guard);
-
-                        enemies.push(
-                            Enemy::New(
-                                EnemyType::GUARD,
-                                spriteLocation,
-                                spriteTile,
-                                RandomUnitVec(),
-                                refreshRate
-                            )
-                        );
-
-                        Tile::EMPTY(EmptyTile::New(Some(sprites)))
-                    },
-                    "OF" => {
-                        let officer = Sprite {
-                            textureHandle: TextureHandle::New(TextureType::OFFICER, 1),
-                            location: spriteLocation
-                        };
-
-                        enemies.push(
-                            Enemy::New(
-                                EnemyType::OFFICER,
-                                spriteLocation,
-                                spriteTile,
-                                RandomUnitVec(),
-                                refreshRate
-                            )
-                        );
+                // Walls and doors fill the whole tile, so nothing else can share it
+                if let Some(wall) = &cell.wall {
+                    if let Some(object) = &cell.object {
+                        return Err(object.origin.Error(path, format!("object on a tile that already has a {}", WallName(wall.value))));
+                    }
+                    if let Some(actor) = &cell.actor {
+                        return Err(actor.origin.Error(path, format!("actor on a tile that already has a {}", WallName(wall.value))));
+                    }
+                }
 
-                        let mut sprites: Vec<Sprite> = Vec::new();
-                            sprites.push(officer);
+                // Enemies get a sprite on their tile right away, so they show up before the first tick
+                let mut enemySprites: Vec<Sprite> = Vec::new();
+                if let Some(actor) = &cell.actor {
+                    let enemyType = match &actor.value {
+                        ActorCode::PLAYER(facing) => {
+                            playerStarts.push((&actor.origin, PlayerStart {
+                                location: spriteLocation,
+                                viewDir: facing.ViewDir()
+                            }));
+                            None
+                        },
+                        ActorCode::ENEMY(enemyCode, routine) => match enemyTypes.Get(enemyCode) {
+                            Some(enemyType) => Some((enemyType, *routine)),
+                            None => return Err(actor.origin.Error(path, format!("unknown enemy `{}` ; {} has {}", enemyCode, enemyTypes.path, enemyTypes.CodeList())))
+                        }
+                    };
 
-                        Tile::EMPTY(EmptyTile::New(Some(sprites)))
-                    },
-                    "SS" => {
-                        let SS = Sprite {
-                            textureHandle: TextureHandle::New(TextureType::SS, 1),
+                    if let Some((enemyType, routine)) = enemyType {
+                        enemySprites.push(Sprite {
+                            textureHandle: TextureHandle::New(enemyType.sprites, 1),
                             location: spriteLocation
-                        };
+                        });
 
                         enemies.push(
                             Enemy::New(
-                                EnemyType::SS,
+                                enemyType,
                                 spriteLocation,
                                 spriteTile,
-                                RandomUnitVec(),
-                                refreshRate
+                                RandomUnitVec(rng),
+                                routine
                             )
                         );
+                    }
+                }
 
-                        let mut sprites: Vec<Sprite> = Vec::new();
-                            sprites.push(SS);
-
-                        Tile::EMPTY(EmptyTile::New(Some(sprites)))
+                tiles[column as usize][row as usize] = match (&cell.wall, &cell.object) {
+                    (Some(wall), _) => match wall.value {
+                        WallCode::WALL(textureID) => {
+                            CheckTextureID(path, "wall", &wall.origin, TextureType::WALL, textureID, textureID+1)?;
+                            Tile::WALL(Wall::New(textureID, textureID+1))
+                        },
+                        WallCode::DOOR => {
+                            doorTileCoords.push(iPoint2::New(column, row));
+                            Tile::DOOR(Door::New())
+                        }
                     },
-                    "O" => {
+                    (None, Some(object)) => {
+                        CheckTextureID(path, "object", &object.origin, TextureType::OBJECT, object.value, object.value)?;
                         let object = Sprite {
-                            textureHandle: TextureHandle::New(TextureType::OBJECT, tileTextureID.unwrap()),
+                            textureHandle: TextureHandle::New(TextureType::OBJECT, object.value),
                             location: spriteLocation
                         };
 
-                        Tile::OBJECT(ObjectTile::New(object))
+                        let mut objectTile = ObjectTile::New(object);
+                        objectTile.enemySprites = enemySprites;
+                        Tile::OBJECT(objectTile)
                     },
-                    _ => panic!()
+                    (None, None) => Tile::EMPTY(EmptyTile::New(if enemySprites.is_empty() { None } else { Some(enemySprites) }))
                 };
             }
         }
 
-        (
+        // Exactly one start ; with several, point at the second one in file order
+        playerStarts.sort_by_key(|(origin, _)| (origin.line, origin.column));
+        let playerStart = match playerStarts.as_slice() {
+            [] => return Err(LoadError::MAP { path: path.to_string(), message: "no player start ; add a `P-N`, `P-E`, `P-S` or `P-W` cell".to_string() }),
+            [(_, playerStart)] => *playerStart,
+            [(first, _), (second, _), ..] => return Err(second.Error(path, format!("second player start ; the first is at {}:{}, a map needs exactly one", first.line, first.column)))
+        };
+
+        let areas = Areas::New(&tiles);
+
+        Ok((
             Self {
+                info: mapFile.info.clone(),
                 tiles,
                 width,
                 height,
+                lighting,
+                playerStart,
                 doorTileCoords,
+                floorTextures,
+                ceilingTextures,
+                flags,
+                areas,
             },
             enemies
-        )
+        ))
 
     }  
 
@@ -135,6 +184,34 @@ impl Map {
         &mut self.tiles[tileCoord.x() as usize][tileCoord.y() as usize]
     }
 
+    pub fn GetFloorTexture(&self, tileCoord: iPoint2) -> Option<TextureHandle> {
+        self.floorTextures[tileCoord.x() as usize][tileCoord.y() as usize]
+    }
+
+    pub fn GetCeilingTexture(&self, tileCoord: iPoint2) -> Option<TextureHandle> {
+        self.ceilingTextures[tileCoord.x() as usize][tileCoord.y() as usize]
+    }
+
+    pub fn GetFlags(&self, tileCoord: iPoint2) -> TileFlags {
+        self.flags[tileCoord.x() as usize][tileCoord.y() as usize]
+    }
+
+    pub fn GetAreas(&self, tileCoord: iPoint2) -> Vec<usize> {
+        self.areas.GetAreas(tileCoord)
+    }
+
+    // Areas a sound made at the tile carries to, indexed by area ; it gets through any door that isn't fully shut
+    pub fn AreasInEarshot(&self, tileCoord: iPoint2) -> Vec<bool> {
+        self.areas.ConnectedAreas(tileCoord, |doorTile| match self.GetTile(doorTile) {
+            Tile::DOOR(door) => door.status != DoorStatus::CLOSED,
+            _ => false
+        })
+    }
+
+    pub fn HasTexturedFlats(&self) -> bool {
+        self.floorTextures.iter().chain(self.ceilingTextures.iter()).flatten().any(|t| t.is_some())
+    }
+
     pub fn WithinMap(&self, tileCoord: iPoint2) -> bool {
         (tileCoord.x() > 0 && tileCoord.x() < self.width-1) && (tileCoord.y() > 0 && tileCoord.y() < self.height-1)
     }
@@ -146,7 +223,7 @@ impl Map {
         let tileWithinMap = self.WithinMap(proposedTileCoord);
         let notPlayerTile = proposedTileCoord != playerTileCoord;
 
-        if tileWithinMap && notPlayerTile {
+        if tileWithinMap && notPlayerTile && !self.GetFlags(proposedTileCoord).blocksEnemies {
             match self.GetTile(proposedTileCoord) {
                 Tile::EMPTY(_) => {
                     return true;
@@ -154,6 +231,9 @@ impl Map {
                 Tile::OBJECT(object) => {
                     return !object.PlayerTileHit();
                 },
+                Tile::DOOR(door) => {
+                    return !door.PlayerTileHit();
+                },
                 Tile::NONE => panic!(),
                 _ => {
                     return false;
@@ -164,12 +244,82 @@ impl Map {
         }
     }
 
-    pub fn UpdateDoors(&mut self, moveIncr: f64, timerIncr: f64, playerLoc: Point2) {
+    // Whether a straight line between two points only crosses open floor, objects and doorways whose gate is out of the way
+    pub fn LineOfSight(&self, from: Point2, to: Point2) -> bool {
+        let targetTile = iPoint2::from(to);
+
+        // Ray math divides by both direction components, so keep either from being exactly zero
+        let mut direction = to - from;
+        if direction.x() == 0.0 { direction.SetX(1e-9); }
+        if direction.y() == 0.0 { direction.SetY(1e-9); }
+
+        let mut rayCursor = RayCursor::New(Ray::New(from, direction), from);
+        while rayCursor.hitTile != targetTile && self.WithinMap(rayCursor.hitTile) {
+            rayCursor.GoToNextHit();
+            match self.GetTile(rayCursor.hitTile) {
+                Tile::EMPTY(_) | Tile::OBJECT(_) => {},
+                Tile::DOOR(door) => {
+                    if door.GetWallSlice(&mut rayCursor).is_some() {
+                        return false;
+                    }
+                },
+                Tile::NONE => panic!(),
+                _ => {
+                    return false;
+                }
+            }
+        }
+
+        rayCursor.hitTile == targetTile
+    }
+
+    // occupiedTiles are where the player and every living enemy are ; doors hold open while any of them is in the doorway
+    pub fn UpdateDoors(&mut self, moveIncr: f64, timerIncr: f64, occupiedTiles: &[iPoint2]) {
         for doorIndex in 0..self.doorTileCoords.len() {
             let doorCoord = self.doorTileCoords[doorIndex];
             if let Tile::DOOR(door) = self.GetMutTile(doorCoord) {
-                door.Update(moveIncr, timerIncr, iPoint2::from(playerLoc) == doorCoord);
+                door.Update(moveIncr, timerIncr, occupiedTiles.contains(&doorCoord));
             }
         }
     }
-}
\ No newline at end of file
+
+    // Starts a door opening if there's one at the tile ; false if there isn't
+    pub fn OpenDoor(&mut self, tileCoord: iPoint2) -> bool {
+        if let Tile::DOOR(door) = self.GetMutTile(tileCoord) {
+            door.Open();
+            true
+        } else {
+            false
+        }
+    }
+
+    // Leaves an object in the middle of an empty tile, keeping any enemy sprites already there ; a tile that already
+    // holds an object, or a doorway, keeps what it has
+    pub fn DropItem(&mut self, tileCoord: iPoint2, objectID: i32) {
+        if let Tile::EMPTY(emptyTile) = self.GetMutTile(tileCoord) {
+            let mut objectTile = ObjectTile::New(Sprite {
+                textureHandle: TextureHandle::New(TextureType::OBJECT, objectID),
+                location: Point2::from(tileCoord) + Vec2::New(0.5, 0.5)
+            });
+            objectTile.enemySprites = std::mem::take(&mut emptyTile.enemySprites);
+            *self.GetMutTile(tileCoord) = Tile::OBJECT(objectTile);
+        }
+    }
+}
+
+fn WallName(wallCode: WallCode) -> &'static str {
+    match wallCode {
+        WallCode::WALL(_) => "wall",
+        WallCode::DOOR => "door"
+    }
+}
+
+// Every texture a cell uses, ID through lastID, has to be on the sheet ; the renderers index the sheets with them unchecked
+fn CheckTextureID(path: &str, what: &str, origin: &CellOrigin, textureType: TextureType, ID: i32, lastID: i32) -> Result<(), LoadError> {
+    let numTextures = TEXTURE_SHEET_SPECS.iter().find(|spec| spec.textureType == textureType).unwrap().numTextures;
+    if ID < 1 || lastID > numTextures {
+        let lastAllowed = numTextures - (lastID - ID);
+        return Err(origin.Error(path, format!("{} texture {} is out of range ; expected 1-{}", what, ID, lastAllowed)));
+    }
+    Ok(())
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use ndarray::Array2;
+use crate::enemy_types::{EnemyTypes, IsEnemyCode};
+use crate::gamemaps::{ImportGameMaps, ParseGameMapsPath};
+use crate::load_error::{CellOrigin, LoadError};
+use crate::utils::csv::{ParseCSV, ParseCSVHeader, ReadLines};
+use crate::utils::vec2d::Vec2;
+
+/*
+=========================================================
+    Layer contents
+=========================================================
+*/
+
+// Layers in the order they're written to a layered map file
+pub const LAYER_NAMES: [&str; 6] = ["walls", "objects", "actors", "floor", "ceiling", "flags"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Facing {
+    NORTH,
+    EAST,
+    SOUTH,
+    WEST
+}
+
+impl Facing {
+    fn Parse(code: &str) -> Result<Self, String> {
+        match code {
+            "N" => Ok(Facing::NORTH),
+            "E" => Ok(Facing::EAST),
+            "S" => Ok(Facing::SOUTH),
+            "W" => Ok(Facing::WEST),
+            _ => Err(format!("player start facing `{}` ; expected N, E, S or W", code))
+        }
+    }
+
+    fn Code(&self) -> &'static str {
+        match self {
+            Facing::NORTH => "N",
+            Facing::EAST => "E",
+            Facing::SOUTH => "S",
+            Facing::WEST => "W"
+        }
+    }
+
+    // North is up in the file, which is +y on the map
+    pub fn ViewDir(&self) -> Vec2 {
+        match self {
+            Facing::NORTH => Vec2::New(0.0, 1.0),
+            Facing::EAST => Vec2::New(1.0, 0.0),
+            Facing::SOUTH => Vec2::New(0.0, -1.0),
+            Facing::WEST => Vec2::New(-1.0, 0.0)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WallCode {
+    WALL(i32),
+    DOOR
+}
+
+// What an enemy does until it notices the player ; written as a `-S` suffix for standing, e.g. `GU-S`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EnemyRoutine {
+    STAND,
+    PATROL
+}
+
+// Enemies are placed by the code of their type in the enemy types file, e.g. `GU` ; see enemy_types.rs
+#[derive(Clone, PartialEq, Debug)]
+pub enum ActorCode {
+    PLAYER(Facing),
+    ENEMY(String, EnemyRoutine)
+}
+
+impl ActorCode {
+    fn Parse(code: &str) -> Result<Self, String> {
+        let (enemyCode, routine) = match code.split_once('-') {
+            Some(("P", facing)) => return Ok(ActorCode::PLAYER(Facing::Parse(facing)?)),
+            None if code == "P" => return Err("player start needs a facing, e.g. `P-N`".to_string()),
+            Some((enemyCode, "S")) => (enemyCode, EnemyRoutine::STAND),
+            None => (code, EnemyRoutine::PATROL),
+            _ => ("", EnemyRoutine::PATROL)
+        };
+        // Whether there's such an enemy type is only known once the map's enemy types file is read
+        if IsEnemyCode(enemyCode) {
+            Ok(ActorCode::ENEMY(enemyCode.to_string(), routine))
+        } else {
+            Err(format!("unknown actor `{}` ; expected P-N, P-E, P-S, P-W, or an enemy code such as GU with an optional `-S` to stand", code))
+        }
+    }
+
+    fn Code(&self) -> String {
+        match self {
+            ActorCode::PLAYER(facing) => format!("P-{}", facing.Code()),
+            ActorCode::ENEMY(enemyCode, EnemyRoutine::STAND) => format!("{}-S", enemyCode),
+            ActorCode::ENEMY(enemyCode, EnemyRoutine::PATROL) => enemyCode.to_string()
+        }
+    }
+}
+
+// Per-tile flags, written as letters in the flags layer
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TileFlags {
+    // `B` ; enemies never walk into the tile, e.g. to keep patrols out of a room
+    pub blocksEnemies: bool
+}
+
+impl TileFlags {
+    fn Parse(code: &str) -> Result<Self, String> {
+        let mut flags = TileFlags::default();
+        for letter in code.chars() {
+            match letter {
+                'B' => flags.blocksEnemies = true,
+                _ => return Err(format!("unknown flag `{}` ; expected B", letter))
+            }
+        }
+        Ok(flags)
+    }
+
+    fn Code(&self) -> String {
+        if self.blocksEnemies { "B".to_string() } else { String::new() }
+    }
+
+    fn IsEmpty(&self) -> bool {
+        *self == TileFlags::default()
+    }
+}
+
+// A value from one layer, along with the cell it was read from
+#[derive(Clone, Debug)]
+pub struct LayerValue<T> {
+    pub value: T,
+    pub origin: CellOrigin
+}
+
+impl<T> LayerValue<T> {
+    pub fn New(value: T, origin: &CellOrigin) -> Option<Self> {
+        Some(Self { value, origin: origin.clone() })
+    }
+}
+
+// Everything the map file says about one tile
+#[derive(Clone, Default, Debug)]
+pub struct MapCell {
+    pub wall: Option<LayerValue<WallCode>>,
+    pub object: Option<LayerValue<i32>>,
+    pub actor: Option<LayerValue<ActorCode>>,
+    pub floor: Option<LayerValue<i32>>,
+    pub ceiling: Option<LayerValue<i32>>,
+    pub flags: TileFlags,
+
+    // Where problems with the tile as a whole are reported ; the CSV cell, or the tile's cell in the walls layer
+    pub origin: CellOrigin
+}
+
+/*
+=========================================================
+    MapFile struct definition
+=========================================================
+*/
+
+#[derive(Clone, Debug)]
+pub struct MapInfo {
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    pub parSeconds: Option<u32>
+}
+
+// A map as written on disk, before any tiles or enemies are built from it ; a layered `.wmap` file, an imported CSV or an
+// imported level of the original game
+pub struct MapFile {
+    pub path: String,
+    pub info: MapInfo,
+
+    // Every other `key = value` map option, e.g. `light.ambient`
+    pub settings: HashMap<String, String>,
+
+    // Indexed [(y, x)], with y = 0 the bottom row of the file, same as the map's y axis
+    pub cells: Array2<MapCell>
+}
+
+impl MapFile {
+    // CSV maps and levels of the original game are imported ; anything else is read as a layered map file
+    pub fn Load(path: &str) -> Result<Self, LoadError> {
+        if let Some((gamemapsPath, level)) = ParseGameMapsPath(path) {
+            ImportGameMaps(gamemapsPath, level)
+        } else if path.ends_with(".csv") {
+            Self::ImportCSV(path)
+        } else {
+            Self::LoadLayered(path)
+        }
+    }
+
+    // The original format, one cell per tile packing every layer into `TYPE-ID|F-ID|C-ID`
+    pub fn ImportCSV(path: &str) -> Result<Self, LoadError> {
+        let fields = ParseCSV(path)?;
+        let settings = ParseCSVHeader(path)?;
+
+        // Enemy codes share the cell with every other tile type, so which ones there are has to be known up front
+        let enemyTypes = EnemyTypes::FromMapHeader(&settings)?;
+        let mut cells: Array2<MapCell> = Array2::default(fields.raw_dim());
+        for (cell, field) in cells.iter_mut().zip(fields.iter()) {
+            *cell = ParseCSVCell(field, &enemyTypes).map_err(|message| field.Error(path, message))?;
+        }
+
+        let name = Path::new(path).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+        let info = MapInfo { name, width: cells.ncols() as i32, height: cells.nrows() as i32, parSeconds: None };
+        Ok(Self { path: path.to_string(), info, settings, cells })
+    }
+
+    // A `[map]` section of `key = value` options, then one comma separated grid per layer section, top row first.
+    // `#` lines are comments and blank lines are skipped
+    fn LoadLayered(path: &str) -> Result<Self, LoadError> {
+        let contents = ReadLines(path)?;
+        let SyntaxError = |line: u64, message: String| LoadError::SYNTAX { path: path.to_string(), line, message };
+
+        let mut options: Vec<(u64, String, String)> = Vec::new();
+        let mut layers: Vec<LayerSection> = Vec::new();
+        let mut seenSections: Vec<&str> = Vec::new();
+
+        for (i, text) in contents.lines().enumerate() {
+            let line = i as u64 + 1;
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(section) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                if section != "map" && !LAYER_NAMES.contains(&section) {
+                    return Err(SyntaxError(line, format!("unknown section `[{}]` ; expected [map] or one of the layers [{}]", section, LAYER_NAMES.join("], ["))));
+                }
+                if seenSections.contains(&section) {
+                    return Err(SyntaxError(line, format!("section `[{}]` appears twice", section)));
+                }
+                if section != "map" {
+                    layers.push((line, section, Vec::new()));
+                }
+                seenSections.push(section);
+                continue;
+            }
+
+            match seenSections.last().copied() {
+                Some("map") => {
+                    let (key, value) = trimmed.split_once('=')
+                        .ok_or_else(|| SyntaxError(line, format!("expected `key = value`, got `{}`", trimmed)))?;
+                    options.push((line, key.trim().to_string(), value.trim().to_string()));
+                },
+                Some(_) => layers.last_mut().unwrap().2.push((line, text)),
+                None => return Err(SyntaxError(line, "expected a section such as `[map]` before any content".to_string()))
+            }
+        }
+
+        // Map options ; name and size are required, the rest is kept for lighting and the like
+        let mut name: Option<String> = None;
+        let mut width: Option<i32> = None;
+        let mut height: Option<i32> = None;
+        let mut parSeconds: Option<u32> = None;
+        let mut settings: HashMap<String, String> = HashMap::new();
+        for (line, key, value) in options {
+            let ParseSize = |value: &str| value.parse::<i32>().ok().filter(|&size| size > 0)
+                .ok_or_else(|| SyntaxError(line, format!("{} `{}` is not a positive whole number", key, value)));
+            match key.as_str() {
+                "name" => name = Some(value),
+                "width" => width = Some(ParseSize(&value)?),
+                "height" => height = Some(ParseSize(&value)?),
+                "par" => parSeconds = Some(ParseParTime(&value).map_err(|message| SyntaxError(line, message))?),
+                _ => { settings.insert(key, value); }
+            }
+        }
+        let MissingOption = |key: &str| LoadError::MAP { path: path.to_string(), message: format!("[map] needs a `{}`", key) };
+        let info = MapInfo {
+            name: name.ok_or_else(|| MissingOption("name"))?,
+            width: width.ok_or_else(|| MissingOption("width"))?,
+            height: height.ok_or_else(|| MissingOption("height"))?,
+            parSeconds
+        };
+        if !layers.iter().any(|(_, name, _)| *name == "walls") {
+            return Err(LoadError::MAP { path: path.to_string(), message: "missing the [walls] layer".to_string() });
+        }
+
+        // Layers ; each is a full grid of the map's size, empty cells meaning nothing on that layer
+        let mut cells: Array2<MapCell> = Array2::default((info.height as usize, info.width as usize));
+        for (sectionLine, layerName, rows) in layers {
+            if rows.len() != info.height as usize {
+                return Err(SyntaxError(sectionLine, format!("[{}] has {} row(s) ; the map is {} tall", layerName, rows.len(), info.height)));
+            }
+            for (rowIndex, (line, text)) in rows.into_iter().enumerate() {
+                let fields: Vec<&str> = text.split(',').collect();
+                if fields.len() != info.width as usize {
+                    return Err(SyntaxError(line, format!("row has {} cell(s) ; the map is {} wide", fields.len(), info.width)));
+                }
+                // Top row of the grid is the highest y
+                let y = info.height as usize - 1 - rowIndex;
+                for (x, field) in fields.into_iter().enumerate() {
+                    let origin = CellOrigin { text: field.to_string(), line, column: x + 1 };
+                    ParseLayerCell(layerName, &mut cells[(y, x)], &origin).map_err(|message| origin.Error(path, message))?;
+                    if layerName == "walls" {
+                        cells[(y, x)].origin = origin;
+                    }
+                }
+            }
+        }
+
+        Ok(Self { path: path.to_string(), info, settings, cells })
+    }
+
+    // Writes the map as a layered file ; layers with nothing on them are left out, apart from the walls
+    pub fn Save(&self, path: &str) -> Result<(), LoadError> {
+        let mut contents = String::new();
+        contents.push_str("[map]\n");
+        contents.push_str(&format!("name = {}\n", self.info.name));
+        contents.push_str(&format!("width = {}\n", self.info.width));
+        contents.push_str(&format!("height = {}\n", self.info.height));
+        if let Some(parSeconds) = self.info.parSeconds {
+            contents.push_str(&format!("par = {}:{:02}\n", parSeconds / 60, parSeconds % 60));
+        }
+        let mut settings: Vec<(&String, &String)> = self.settings.iter().collect();
+        settings.sort();
+        for (key, value) in settings {
+            contents.push_str(&format!("{} = {}\n", key, value));
+        }
+
+        for layerName in LAYER_NAMES {
+            let codes: Array2<String> = self.cells.map(|cell| LayerCode(layerName, cell));
+            if layerName != "walls" && codes.iter().all(|code| code.is_empty()) {
+                continue;
+            }
+            contents.push_str(&format!("\n[{}]\n", layerName));
+            // Top row of the file is the highest y
+            for y in (0..codes.nrows()).rev() {
+                contents.push_str(&codes.row(y).to_vec().join(","));
+                contents.push('\n');
+            }
+        }
+
+        fs::write(path, contents).map_err(|error| LoadError::IO { path: path.to_string(), error })
+    }
+}
+
+// A layer section as read from the file ; the line of its `[name]`, its name, and the line and text of each row
+type LayerSection<'a> = (u64, &'a str, Vec<(u64, &'a str)>);
+
+/*
+=========================================================
+    Cell parsing
+=========================================================
+*/
+
+fn ParseTextureID(part: &str) -> Result<i32, String> {
+    part.parse::<i32>().map_err(|_| format!("texture number `{}` is not a number", part))
+}
+
+// `M:SS`
+fn ParseParTime(value: &str) -> Result<u32, String> {
+    let (minutes, seconds) = value.split_once(':').ok_or_else(|| format!("par `{}` ; expected minutes and seconds, e.g. `1:30`", value))?;
+    match (minutes.trim().parse::<u32>(), seconds.trim().parse::<u32>()) {
+        (Ok(minutes), Ok(seconds)) if seconds < 60 => Ok(minutes*60 + seconds),
+        _ => Err(format!("par `{}` ; expected minutes and seconds, e.g. `1:30`", value))
+    }
+}
+
+// A CSV cell, written as `TYPE-ID`, optionally followed by `|F-ID` and/or `|C-ID` for its floor and ceiling textures.
+// The player start is the exception, `P-N`, `P-E`, `P-S` or `P-W`, where the part after the type is the facing instead,
+// and so are enemies, placed by any code in the map's enemy types file, whose only suffix is an optional `-S` to stand.
+fn ParseCSVCell(field: &CellOrigin, enemyTypes: &EnemyTypes) -> Result<MapCell, String> {
+    let mut sections = field.text.trim().split('|');
+    let mut cell = MapCell { origin: field.clone(), ..Default::default() };
+
+    // Split the tile section into parts by '-' ; first part is the type, second (if present) the texture ID
+    let tileSection = sections.next().unwrap_or("");
+    let parts: Vec<&str> = tileSection.split('-').collect();
+    if parts.len() > 2 {
+        return Err("expected `TYPE` or `TYPE-ID`".to_string());
+    }
+    let tileType = parts[0];
+    let isActor = tileType == "P" || IsEnemyCode(tileType);
+    let textureID = if isActor { None } else { parts.get(1).map(|part| ParseTextureID(part)).transpose()? };
+    let RequireTextureID = |example: &str| textureID.ok_or_else(|| format!("needs a texture number, e.g. `{}`", example));
+
+    match tileType {
+        "" => {},
+        "W" => cell.wall = LayerValue::New(WallCode::WALL(RequireTextureID("W-15")?), field),
+        "D" => cell.wall = LayerValue::New(WallCode::DOOR, field),
+        "O" => cell.object = LayerValue::New(RequireTextureID("O-19")?, field),
+        "P" => cell.actor = LayerValue::New(ActorCode::Parse(tileSection)?, field),
+        enemyCode if isActor && enemyTypes.Get(enemyCode).is_some() => cell.actor = LayerValue::New(ActorCode::Parse(tileSection)?, field),
+        other => return Err(format!("unknown tile type `{}` ; expected W, O, D, P, an enemy from {} ({}) or an empty cell", other, enemyTypes.path, enemyTypes.CodeList()))
+    }
+
+    for section in sections {
+        let (kind, part) = section.split_once('-')
+            .ok_or_else(|| format!("`|{}` needs a texture number, e.g. `|F-23`", section))?;
+        let textureID = LayerValue::New(ParseTextureID(part)?, field);
+        match kind {
+            "F" => cell.floor = textureID,
+            "C" => cell.ceiling = textureID,
+            _ => return Err(format!("unknown section `|{}` ; expected `|F-ID` or `|C-ID`", section))
+        }
+    }
+
+    Ok(cell)
+}
+
+// One cell of a layer section ; its meaning depends on the layer
+fn ParseLayerCell(layerName: &str, cell: &mut MapCell, origin: &CellOrigin) -> Result<(), String> {
+    let code = origin.text.trim();
+    if code.is_empty() {
+        return Ok(());
+    }
+
+    match layerName {
+        "walls" => cell.wall = match code {
+            "D" => LayerValue::New(WallCode::DOOR, origin),
+            _ => LayerValue::New(WallCode::WALL(code.parse::<i32>().map_err(|_| format!("expected a wall texture number or `D`, got `{}`", code))?), origin)
+        },
+        "objects" => cell.object = LayerValue::New(ParseTextureID(code)?, origin),
+        "actors" => cell.actor = LayerValue::New(ActorCode::Parse(code)?, origin),
+        "floor" => cell.floor = LayerValue::New(ParseTextureID(code)?, origin),
+        "ceiling" => cell.ceiling = LayerValue::New(ParseTextureID(code)?, origin),
+        "flags" => cell.flags = TileFlags::Parse(code)?,
+        _ => unreachable!()
+    }
+    Ok(())
+}
+
+// The inverse of ParseLayerCell, for saving
+fn LayerCode(layerName: &str, cell: &MapCell) -> String {
+    match layerName {
+        "walls" => match cell.wall.as_ref().map(|wall| wall.value) {
+            Some(WallCode::WALL(ID)) => ID.to_string(),
+            Some(WallCode::DOOR) => "D".to_string(),
+            None => String::new()
+        },
+        "objects" => cell.object.as_ref().map_or(String::new(), |object| object.value.to_string()),
+        "actors" => cell.actor.as_ref().map_or(String::new(), |actor| actor.value.Code()),
+        "floor" => cell.floor.as_ref().map_or(String::new(), |floor| floor.value.to_string()),
+        "ceiling" => cell.ceiling.as_ref().map_or(String::new(), |ceiling| ceiling.value.to_string()),
+        "flags" => if cell.flags.IsEmpty() { String::new() } else { cell.flags.Code() },
+        _ => unreachable!()
+    }
+}
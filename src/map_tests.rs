@@ -0,0 +1,428 @@
+/*
+=========================================================
+    Map loading tests
+
+    Broken maps must come back as a LoadError pointing at the offending
+    file, line, column and cell, instead of panicking. Maps that load but
+    break the engine's assumptions must be caught by the validator.
+=========================================================
+*/
+
+use rand::{rngs::StdRng, SeedableRng};
+use crate::load_error::LoadError;
+use crate::enemy::EnemyState;
+use crate::gamemaps::{CarmackExpand, RLEWExpand};
+use crate::map::Map;
+use crate::map_file::MapFile;
+use crate::map_validator::ValidateMap;
+use crate::tiles::Tile;
+use crate::utils::scratch::{ScratchPath, WriteScratchFile};
+use crate::utils::vec2d::{iPoint2, Point2};
+
+const SCRATCH_DIR: &str = "target/map_tests";
+
+fn LoadMapText(name: &str, contents: &str) -> Result<Map, LoadError> {
+    LoadMapFileText(&format!("{}.csv", name), contents)
+}
+
+// fileName picks the format, `.csv` or `.wmap`
+fn LoadMapFileText(fileName: &str, contents: &str) -> Result<Map, LoadError> {
+    let path = WriteScratchFile(SCRATCH_DIR, fileName, contents);
+    Map::Load(&path, &mut StdRng::seed_from_u64(0)).map(|(map, _)| map)
+}
+
+fn AssertCellError(result: Result<Map, LoadError>, expectedLine: u64, expectedColumn: usize, expectedCell: &str) {
+    match result {
+        Err(LoadError::CELL { line, column, cell, .. }) => {
+            assert_eq!((line, column, cell.as_str()), (expectedLine, expectedColumn, expectedCell));
+        },
+        Err(e) => panic!("expected a cell error, got: {}", e),
+        Ok(_) => panic!("expected a cell error, but the map loaded")
+    }
+}
+
+#[test]
+fn wall_without_texture_number_reports_its_cell() {
+    let result = LoadMapText("wall_without_texture", "\
+# light.ambient = 1.0
+W-15,W-15,W-15
+W-15,,W
+W-15,W-15,W-15
+");
+    AssertCellError(result, 3, 3, "W");
+}
+
+#[test]
+fn unknown_tile_type_and_bad_sections_report_their_cell() {
+    AssertCellError(LoadMapText("unknown_tile_type", "W-15,W-15,W-15\nW-15,X-4,W-15\nW-15,W-15,W-15\n"), 2, 2, "X-4");
+    AssertCellError(LoadMapText("bad_texture_number", "W-15,W-15,W-15\nW-15,O-l9,W-15\nW-15,W-15,W-15\n"), 2, 2, "O-l9");
+    AssertCellError(LoadMapText("bad_flat_section", "W-15,W-15,W-15\nW-15,|G-23,W-15\nW-15,W-15,W-15\n"), 2, 2, "|G-23");
+}
+
+#[test]
+fn texture_ids_off_their_sheet_report_their_cell() {
+    AssertCellError(LoadMapText("wall_zero", "W-15,W-15,W-15\nW-15,W-0,W-15\nW-15,W-15,W-15\n"), 2, 2, "W-0");
+    AssertCellError(LoadMapText("object_zero", "W-15,W-15,W-15\nW-15,O-0,W-15\nW-15,W-15,W-15\n"), 2, 2, "O-0");
+    AssertCellError(LoadMapText("object_past_sheet", "W-15,W-15,W-15\nW-15,O-51,W-15\nW-15,W-15,W-15\n"), 2, 2, "O-51");
+    AssertCellError(LoadMapFileText("wall_past_sheet.wmap", &LAYERED_ROOM.replace("15,15,15,15,15\n\n[objects]", "15,15,15,15,200\n\n[objects]")), 13, 5, "200");
+    AssertCellError(LoadMapText("floor_zero", "W-15,W-15,W-15\nW-15,|F-0,W-15\nW-15,W-15,W-15\n"), 2, 2, "|F-0");
+    AssertCellError(LoadMapText("floor_past_sheet", "W-15,W-15,W-15\nW-15,|F-111,W-15\nW-15,W-15,W-15\n"), 2, 2, "|F-111");
+
+    // Walls and ceilings show the unlit texture after their ID as well, so the sheet's last texture can't be one
+    match LoadMapText("wall_on_last_texture", "W-15,W-15,W-15\nW-15,W-110,W-15\nW-15,W-15,W-15\n") {
+        Err(e @ LoadError::CELL { .. }) => assert!(e.to_string().ends_with("cell `W-110`: wall texture 110 is out of range ; expected 1-109"), "{}", e),
+        Err(e) => panic!("expected a cell error, got: {}", e),
+        Ok(_) => panic!("expected a cell error, but the map loaded")
+    }
+    match LoadMapText("ceiling_past_sheet", "W-15,W-15,W-15\nW-15,|F-110|C-110,W-15\nW-15,W-15,W-15\n") {
+        Err(e @ LoadError::CELL { .. }) => assert!(e.to_string().ends_with("cell `|F-110|C-110`: ceiling texture 110 is out of range ; expected 1-109"), "{}", e),
+        Err(e) => panic!("expected a cell error, got: {}", e),
+        Ok(_) => panic!("expected a cell error, but the map loaded")
+    }
+}
+
+#[test]
+fn player_start_sets_location_and_facing() {
+    let map = LoadMapText("player_start", "W-15,W-15,W-15,W-15\nW-15,,P-E,W-15\nW-15,,,W-15\nW-15,W-15,W-15,W-15\n").unwrap();
+    assert_eq!((map.playerStart.location.x(), map.playerStart.location.y()), (2.5, 2.5));
+    assert_eq!((map.playerStart.viewDir.x(), map.playerStart.viewDir.y()), (1.0, 0.0));
+
+    AssertCellError(LoadMapText("bad_facing", "W-15,W-15,W-15\nW-15,P-Q,W-15\nW-15,W-15,W-15\n"), 2, 2, "P-Q");
+    AssertCellError(LoadMapText("two_starts", "W-15,W-15,W-15,W-15\nW-15,P-N,,W-15\nW-15,,P-S,W-15\nW-15,W-15,W-15,W-15\n"), 3, 3, "P-S");
+    match LoadMapText("no_start", "W-15,W-15,W-15\nW-15,,W-15\nW-15,W-15,W-15\n") {
+        Err(e @ LoadError::MAP { .. }) => assert!(e.to_string().contains("no player start")),
+        _ => panic!("expected a missing player start error")
+    }
+}
+
+#[test]
+fn bad_header_and_missing_file_report_the_file() {
+    let result = LoadMapText("bad_header", "# fog.color = 10 20\nW-15,W-15\nW-15,W-15\n");
+    assert!(matches!(result, Err(LoadError::HEADER { .. })), "expected a header error");
+
+    let result = Map::Load("tests/maps/does_not_exist.csv", &mut StdRng::seed_from_u64(0));
+    match result {
+        Err(e @ LoadError::IO { .. }) => assert!(e.to_string().starts_with("tests/maps/does_not_exist.csv: ")),
+        _ => panic!("expected an IO error")
+    }
+}
+
+/*
+=========================================================
+    Layered map format tests
+=========================================================
+*/
+
+const LAYERED_ROOM: &str = "\
+# A 5x4 room
+[map]
+name = Side Room
+width = 5
+height = 4
+par = 1:05
+light.ambient = 0.5
+
+[walls]
+15,15,15,15,15
+15,,,,D
+15,,,,15
+15,15,15,15,15
+
+[objects]
+,,,,
+,,19,,
+,,,,
+,,,,
+
+[actors]
+,,,,
+,P-E,,GU,
+,,,,
+,,,,
+
+[floor]
+,,,,
+,23,,,
+,,,,
+,,,,
+
+[flags]
+,,,,
+,,,,
+,,,B,
+,,,,
+";
+
+#[test]
+fn layered_map_loads_every_layer() {
+    let map = LoadMapFileText("side_room.wmap", LAYERED_ROOM).unwrap();
+    assert_eq!((map.info.name.as_str(), map.info.width, map.info.height, map.info.parSeconds), ("Side Room", 5, 4, Some(65)));
+    assert_eq!(map.lighting.ambient, 0.5);
+    assert_eq!((map.playerStart.location.x(), map.playerStart.location.y()), (1.5, 2.5));
+    assert!(map.GetFloorTexture(iPoint2::New(1, 2)).is_some());
+
+    // Flag `B` keeps enemies out of (3, 1), the tile right below the guard
+    assert!(!map.ValidEnemyLocation(Point2::New(3.5, 1.5), map.playerStart.location));
+    assert!(map.ValidEnemyLocation(Point2::New(2.5, 1.5), map.playerStart.location));
+
+    // The validator points at the walls layer for whole-tile problems
+    let problems: Vec<String> = ValidateMap(&format!("{}/side_room.wmap", SCRATCH_DIR)).unwrap().iter().map(|problem| problem.to_string()).collect();
+    assert_eq!(problems, vec![format!("{}/side_room.wmap:11:5: cell `D`: map border must be a wall", SCRATCH_DIR)]);
+}
+
+#[test]
+fn layered_map_errors_point_at_their_layer() {
+    let result = LoadMapFileText("short_row.wmap", &LAYERED_ROOM.replace("15,,,,D", "15,,,D"));
+    match result {
+        Err(LoadError::SYNTAX { line, .. }) => assert_eq!(line, 11),
+        _ => panic!("expected a syntax error")
+    }
+
+    AssertCellError(LoadMapFileText("bad_flag.wmap", &LAYERED_ROOM.replace(",,,B,", ",,,Q,")), 36, 4, "Q");
+    AssertCellError(LoadMapFileText("object_in_wall.wmap", &LAYERED_ROOM.replace(",,19,,", "7,,19,,")), 17, 1, "7");
+    AssertCellError(LoadMapFileText("bad_actor.wmap", &LAYERED_ROOM.replace(",P-E,,GU,", ",P-E,,GX,")), 23, 4, "GX");
+}
+
+#[test]
+fn csv_import_saves_as_an_identical_layered_map() {
+    let imported = MapFile::Load("tests/maps/textured_flats.csv").unwrap();
+    let savedPath = ScratchPath(SCRATCH_DIR, "textured_flats.wmap");
+    imported.Save(&savedPath).unwrap();
+    let saved = MapFile::Load(&savedPath).unwrap();
+
+    assert_eq!(saved.settings, imported.settings);
+    assert_eq!(saved.cells.dim(), imported.cells.dim());
+    for (a, b) in saved.cells.iter().zip(imported.cells.iter()) {
+        assert_eq!(a.wall.as_ref().map(|v| v.value), b.wall.as_ref().map(|v| v.value));
+        assert_eq!(a.object.as_ref().map(|v| v.value), b.object.as_ref().map(|v| v.value));
+        assert_eq!(a.actor.as_ref().map(|v| &v.value), b.actor.as_ref().map(|v| &v.value));
+        assert_eq!(a.floor.as_ref().map(|v| v.value), b.floor.as_ref().map(|v| v.value));
+        assert_eq!(a.ceiling.as_ref().map(|v| v.value), b.ceiling.as_ref().map(|v| v.value));
+        assert_eq!(a.flags, b.flags);
+    }
+}
+
+/*
+=========================================================
+    Map validation tests
+=========================================================
+*/
+
+// A walled 24x5 room with the player start at (22, 2), with cells overridden at map coordinates (x, y)
+fn RoomMapText(overrides: &[(usize, usize, &str)]) -> String {
+    let (width, height) = (24, 5);
+    let mut rows: Vec<Vec<String>> = (0..height).map(|y| {
+        (0..width).map(|x| if x == 0 || y == 0 || x == width-1 || y == height-1 { "W-15".to_string() } else { String::new() }).collect()
+    }).collect();
+    rows[2][22] = "P-N".to_string();
+    for &(x, y, cell) in overrides {
+        rows[y][x] = cell.to_string();
+    }
+    // Line 1 of the file is the map's top row, i.e. the highest y
+    rows.iter().rev().map(|row| row.join(",") + "\n").collect()
+}
+
+fn ValidateMapText(name: &str, contents: &str) -> Vec<String> {
+    let path = WriteScratchFile(SCRATCH_DIR, &format!("{}.csv", name), contents);
+    ValidateMap(&path).unwrap().iter().map(|problem| problem.to_string().replace(&format!("{}:", path), "")).collect()
+}
+
+#[test]
+fn shipped_map_validates_clean() {
+    let problems: Vec<String> = ValidateMap("assets/map.wmap").unwrap().iter().map(|problem| problem.to_string()).collect();
+    assert!(problems.is_empty(), "assets/map.wmap has problems:\n{}", problems.join("\n"));
+    assert!(ValidateMapText("room", &RoomMapText(&[])).is_empty());
+}
+
+#[test]
+fn validation_reports_each_problem_at_its_cell() {
+    let problems = ValidateMapText("broken_room", &RoomMapText(&[
+        (5, 4, ""),             // hole in the top border
+        (8, 2, "D"),            // door standing in the open
+        (19, 1, "W-15"), (19, 2, "W-15"), (19, 3, "W-15"),
+        (20, 1, "W-15"), (20, 2, "W-15"), (20, 3, "W-15")
+    ]));
+
+    assert_eq!(problems, vec![
+        "1:6: cell ``: map border must be a wall",
+        "2:2: cell ``: area of 54 tile(s) can't be reached from the player start",
+        "3:9: cell `D`: door needs walls on both sides"
+    ]);
+
+    // A guard placed on a barrel, reported at its cell in the actors layer
+    let path = WriteScratchFile(SCRATCH_DIR, "guard_on_barrel.wmap", LAYERED_ROOM.replace(",,19,,", ",,19,4,"));
+    let problems: Vec<String> = ValidateMap(&path).unwrap().iter().map(|problem| problem.to_string().replace(&format!("{}:", path), "")).collect();
+    assert_eq!(problems, vec![
+        "11:5: cell `D`: map border must be a wall",
+        "23:4: cell `GU`: Guard is placed inside a solid tile"
+    ]);
+}
+
+
+/*
+=========================================================
+    Original game level tests
+
+    The fixtures are built here, compressed the way the original tools
+    compress them, rather than shipping any of the game's data files
+=========================================================
+*/
+
+const RLEW_TAG: u16 = 0xABCD;
+
+fn RLEWCompress(words: &[u16]) -> Vec<u16> {
+    let mut out: Vec<u16> = vec![(words.len()*2) as u16];
+    let mut i = 0;
+    while i < words.len() {
+        let run = words[i..].iter().take_while(|&&w| w == words[i]).count();
+        if run > 3 || words[i] == RLEW_TAG {
+            out.extend([RLEW_TAG, run as u16, words[i]]);
+        } else {
+            out.extend(std::iter::repeat_n(words[i], run));
+        }
+        i += run;
+    }
+    out
+}
+
+// Greedy ; the longest earlier match becomes a near copy when it's close enough, a far copy otherwise
+fn CarmackCompress(words: &[u16]) -> Vec<u8> {
+    let mut out: Vec<u8> = ((words.len()*2) as u16).to_le_bytes().to_vec();
+    let mut i = 0;
+    while i < words.len() {
+        let (mut bestStart, mut bestLength) = (0, 0);
+        for start in 0..i {
+            let length = (0..(words.len() - i).min(255)).take_while(|&k| words[start + k] == words[i + k]).count();
+            if length > bestLength {
+                (bestStart, bestLength) = (start, length);
+            }
+        }
+
+        if bestLength >= 2 && i - bestStart <= 255 {
+            out.extend([bestLength as u8, 0xA7, (i - bestStart) as u8]);
+        } else if bestLength >= 3 {
+            out.extend([bestLength as u8, 0xA8]);
+            out.extend((bestStart as u16).to_le_bytes());
+        } else {
+            let [low, high] = words[i].to_le_bytes();
+            if high == 0xA7 || high == 0xA8 {
+                out.extend([0, high, low]);
+            } else {
+                out.extend([low, high]);
+            }
+            bestLength = 1;
+        }
+        i += bestLength;
+    }
+    out
+}
+
+// Writes MAPHEAD.TST and GAMEMAPS.TST with one level ; planes are given north row first
+fn WriteGameMaps(name: &str, width: usize, height: usize, planes: [&[u16]; 2]) -> String {
+    let dir = format!("{}/{}", SCRATCH_DIR, name);
+
+    let mut gamemaps: Vec<u8> = b"TED5v1.0".to_vec();
+    let mut planeOffsets: Vec<u32> = Vec::new();
+    let mut planeLengths: Vec<u16> = Vec::new();
+    for plane in planes {
+        let compressed = CarmackCompress(&RLEWCompress(plane));
+        planeOffsets.push(gamemaps.len() as u32);
+        planeLengths.push(compressed.len() as u16);
+        gamemaps.extend(compressed);
+    }
+
+    let headerOffset = gamemaps.len() as u32;
+    for offset in [planeOffsets[0], planeOffsets[1], 0] {
+        gamemaps.extend(offset.to_le_bytes());
+    }
+    for length in [planeLengths[0], planeLengths[1], 0] {
+        gamemaps.extend(length.to_le_bytes());
+    }
+    gamemaps.extend((width as u16).to_le_bytes());
+    gamemaps.extend((height as u16).to_le_bytes());
+    let mut levelName = b"Test Level".to_vec();
+    levelName.resize(16, 0);
+    gamemaps.extend(levelName);
+    gamemaps.extend(b"!ID!");
+
+    let mut maphead: Vec<u8> = RLEW_TAG.to_le_bytes().to_vec();
+    maphead.extend(headerOffset.to_le_bytes());
+    maphead.resize(2 + 4*100, 0);
+
+    WriteScratchFile(&dir, "MAPHEAD.TST", maphead);
+    WriteScratchFile(&dir, "GAMEMAPS.TST", gamemaps)
+}
+
+#[test]
+fn carmack_and_rlew_expand_hand_built_data() {
+    // 1 2 3, a near copy of those 3, an escaped 0xA705, then a far copy of the first 2
+    let carmack = [18, 0, 1, 0, 2, 0, 3, 0, 3, 0xA7, 3, 0, 0xA7, 5, 2, 0xA8, 0, 0];
+    assert_eq!(CarmackExpand(&carmack).unwrap(), vec![1, 2, 3, 1, 2, 3, 0xA705, 1, 2]);
+
+    let rlew = [12, 7, RLEW_TAG, 4, 9, 8];
+    assert_eq!(RLEWExpand(&rlew, RLEW_TAG, 6).unwrap(), vec![7, 9, 9, 9, 9, 8]);
+
+    let plane: Vec<u16> = (0..300).map(|i| [8, 8, 8, 107, 0xA712, 0xA800, RLEW_TAG][i % 7] + (i / 50) as u16).collect();
+    let expanded = CarmackExpand(&CarmackCompress(&RLEWCompress(&plane))).unwrap();
+    assert_eq!(RLEWExpand(&expanded, RLEW_TAG, plane.len()).unwrap(), plane);
+}
+
+#[test]
+fn original_level_translates_to_tiles_and_enemies() {
+    let walls: [u16; 48] = [
+        8,   8,   8,   8,   8,   8,   8,   8,
+        8,   107, 107, 107, 8,   107, 107, 8,
+        8,   107, 107, 107, 90,  107, 107, 8,
+        8,   107, 107, 107, 8,   107, 107, 8,
+        8,   106, 107, 107, 8,   107, 107, 8,
+        8,   8,   8,   8,   8,   8,   8,   8
+    ];
+    let objects: [u16; 48] = [
+        0,   0,   0,   0,   0,   0,   0,   0,
+        0,   23,  0,   0,   0,   0,   0,   0,
+        0,   0,   20,  0,   0,   0,   0,   0,
+        0,   0,   0,   0,   0,   108, 0,   0,
+        0,   0,   0,   0,   0,   134, 0,   0,
+        0,   0,   0,   0,   0,   0,   0,   0
+    ];
+    let path = WriteGameMaps("level", 8, 6, [&walls, &objects]);
+    let (map, enemies) = Map::Load(&format!("{}:1", path), &mut StdRng::seed_from_u64(0)).unwrap();
+
+    assert_eq!((map.info.name.as_str(), map.width, map.height), ("Test Level", 8, 6));
+    assert_eq!((map.playerStart.location.x(), map.playerStart.location.y()), (2.5, 3.5));
+    assert_eq!((map.playerStart.viewDir.x(), map.playerStart.viewDir.y()), (1.0, 0.0));
+    assert!(matches!(map.GetTile(iPoint2::New(0, 0)), Tile::WALL(_)));
+    assert!(matches!(map.GetTile(iPoint2::New(4, 3)), Tile::DOOR(_)));
+    assert!(matches!(map.GetTile(iPoint2::New(1, 4)), Tile::OBJECT(_)));
+    assert!(matches!(map.GetTile(iPoint2::New(1, 1)), Tile::EMPTY(_)));
+
+    // The dog isn't supported yet, so only the guard comes through ; 108 is a standing one
+    assert_eq!(enemies.iter().map(|e| (e.enemyType.code.as_str(), e.tile, e.currState)).collect::<Vec<_>>(), vec![("GU", iPoint2::New(5, 2), EnemyState::STAND)]);
+}
+
+#[test]
+fn original_level_errors_name_the_level_and_cell() {
+    let mut walls = [8u16; 9];
+    walls[4] = 75;
+    let objects = [0, 0, 0, 0, 19, 0, 0, 0, 0];
+    let path = WriteGameMaps("bad_level", 3, 3, [&walls, &objects]);
+
+    match Map::Load(&path, &mut StdRng::seed_from_u64(0)) {
+        Err(LoadError::CELL { path: errorPath, line, column, cell, .. }) => {
+            assert_eq!((errorPath, line, column, cell.as_str()), (format!("{}:1", path), 2, 2, "75/19"));
+        },
+        _ => panic!("expected a cell error")
+    }
+    match Map::Load(&format!("{}:3", path), &mut StdRng::seed_from_u64(0)) {
+        Err(e @ LoadError::MAP { .. }) => assert!(e.to_string().ends_with("no such level ; target/map_tests/bad_level/GAMEMAPS.TST has levels 1-1"), "{}", e),
+        _ => panic!("expected a missing level error")
+    }
+
+    // A wall of the original game whose textures the wall sheet doesn't go up to
+    walls[4] = 56;
+    let path = WriteGameMaps("wall_past_sheet", 3, 3, [&walls, &objects]);
+    match Map::Load(&path, &mut StdRng::seed_from_u64(0)) {
+        Err(e @ LoadError::CELL { .. }) => assert!(e.to_string().ends_with("cell `56/19`: wall tile 56 needs wall textures 111-112 ; the wall sheet has 110"), "{}", e),
+        _ => panic!("expected a cell error")
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::fmt;
+use ndarray::Array2;
+use rand::{rngs::StdRng, SeedableRng};
+use crate::enemy::Enemy;
+use crate::load_error::LoadError;
+use crate::load_error::CellOrigin;
+use crate::map::Map;
+use crate::map_file::{ActorCode, MapCell, MapFile};
+use crate::tiles::Tile;
+use crate::utils::vec2d::iPoint2;
+
+/*
+=========================================================
+    MapProblem struct definition
+=========================================================
+*/
+
+// Something wrong with a map that loads fine but would misbehave in game ; points at the cell like a load error does
+pub struct MapProblem {
+    pub path: String,
+    pub line: u64,
+    pub column: usize,
+    pub cell: String,
+    pub message: String
+}
+
+impl MapProblem {
+    fn New(path: &str, origin: &CellOrigin, message: String) -> Self {
+        Self {
+            path: path.to_string(),
+            line: origin.line,
+            column: origin.column,
+            cell: origin.text.clone(),
+            message
+        }
+    }
+}
+
+impl fmt::Display for MapProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: cell `{}`: {}", self.path, self.line, self.column, self.cell, self.message)
+    }
+}
+
+/*
+=========================================================
+    Validation
+=========================================================
+*/
+
+// Loads the map through the real loader, then checks everything the engine silently assumes about it.
+// Problems come back sorted by where they are in the file ; a map that doesn't load at all is a LoadError instead.
+pub fn ValidateMap(mapPath: &str) -> Result<Vec<MapProblem>, LoadError> {
+    let mapFile = MapFile::Load(mapPath)?;
+    let (map, enemies): (Map, Vec<Enemy>) = Map::FromMapFile(&mapFile, &mut StdRng::seed_from_u64(0))?;
+    let validator = MapValidator { mapPath, map: &map, cells: &mapFile.cells };
+
+    let mut problems: Vec<MapProblem> = Vec::new();
+    validator.CheckBorders(&mut problems);
+    validator.CheckDoors(&mut problems);
+    validator.CheckActors(&enemies, &mut problems);
+    validator.CheckReachability(&mut problems);
+
+    problems.sort_by_key(|problem| (problem.line, problem.column));
+    Ok(problems)
+}
+
+struct MapValidator<'a> {
+    mapPath: &'a str,
+    map: &'a Map,
+    cells: &'a Array2<MapCell>
+}
+
+impl MapValidator<'_> {
+    // Cells are stored with the file's last line as row 0, same as the map's y axis
+    fn Cell(&self, tileCoord: iPoint2) -> &MapCell {
+        &self.cells[(tileCoord.y() as usize, tileCoord.x() as usize)]
+    }
+
+    fn TileCoords(&self) -> impl Iterator<Item = iPoint2> + '_ {
+        (0..self.map.height).flat_map(move |y| (0..self.map.width).map(move |x| iPoint2::New(x, y)))
+    }
+
+    fn IsWall(&self, tileCoord: iPoint2) -> bool {
+        matches!(self.map.GetTile(tileCoord), Tile::WALL(_))
+    }
+
+    fn IsWalkable(&self, tileCoord: iPoint2) -> bool {
+        match self.map.GetTile(tileCoord) {
+            Tile::EMPTY(_) | Tile::DOOR(_) => true,
+            Tile::OBJECT(object) => !object.PlayerTileHit(),
+            _ => false
+        }
+    }
+
+    // Rays and movement stop at the map's edge only because WithinMap assumes it is all wall
+    fn CheckBorders(&self, problems: &mut Vec<MapProblem>) {
+        for tileCoord in self.TileCoords() {
+            let onBorder = tileCoord.x() == 0 || tileCoord.y() == 0 || tileCoord.x() == self.map.width-1 || tileCoord.y() == self.map.height-1;
+            if onBorder && !self.IsWall(tileCoord) {
+                problems.push(MapProblem::New(self.mapPath, &self.Cell(tileCoord).origin, "map border must be a wall".to_string()));
+            }
+        }
+    }
+
+    // A door slides into the walls on either side of it, east-west or north-south
+    fn CheckDoors(&self, problems: &mut Vec<MapProblem>) {
+        for tileCoord in self.TileCoords() {
+            if !matches!(self.map.GetTile(tileCoord), Tile::DOOR(_)) || !self.map.WithinMap(tileCoord) {
+                continue;
+            }
+            let (x, y) = (tileCoord.x(), tileCoord.y());
+            let eastWest = self.IsWall(iPoint2::New(x-1, y)) && self.IsWall(iPoint2::New(x+1, y));
+            let northSouth = self.IsWall(iPoint2::New(x, y-1)) && self.IsWall(iPoint2::New(x, y+1));
+            if !eastWest && !northSouth {
+                problems.push(MapProblem::New(self.mapPath, &self.Cell(tileCoord).origin, "door needs walls on both sides".to_string()));
+            }
+        }
+    }
+
+    // Actors standing inside solid objects can't move, and the player would be stuck ; the loader already rejects walls
+    fn CheckActors(&self, enemies: &[Enemy], problems: &mut Vec<MapProblem>) {
+        for e in enemies {
+            if !self.IsWalkable(e.tile) {
+                let cell = self.Cell(e.tile);
+                let origin = cell.actor.as_ref().map_or(&cell.origin, |actor| &actor.origin);
+                problems.push(MapProblem::New(self.mapPath, origin, format!("{} is placed inside a solid tile", e.enemyType.name)));
+            }
+        }
+
+        let spawnTile: iPoint2 = self.map.playerStart.location.into();
+        if !self.IsWalkable(spawnTile) {
+            if let Some(actor) = self.Cell(spawnTile).actor.as_ref().filter(|actor| matches!(actor.value, ActorCode::PLAYER(_))) {
+                problems.push(MapProblem::New(self.mapPath, &actor.origin, "player start is inside a solid tile".to_string()));
+            }
+        }
+    }
+
+    // Flood fills from the player start ; every walkable region it doesn't reach is reported once, at its first cell in the file.
+    // Border cells are left out, CheckBorders already reports those
+    fn CheckReachability(&self, problems: &mut Vec<MapProblem>) {
+        let spawnTile: iPoint2 = self.map.playerStart.location.into();
+        let mut visited: Array2<bool> = Array2::from_elem((self.map.height as usize, self.map.width as usize), false);
+        self.FloodFill(spawnTile, &mut visited);
+
+        for tileCoord in self.TileCoords() {
+            if visited[(tileCoord.y() as usize, tileCoord.x() as usize)] || !self.map.WithinMap(tileCoord) || !self.IsWalkable(tileCoord) {
+                continue;
+            }
+            let region = self.FloodFill(tileCoord, &mut visited);
+            let firstInFile = region.iter()
+                .map(|&t| &self.Cell(t).origin)
+                .min_by_key(|origin| (origin.line, origin.column))
+                .unwrap();
+            problems.push(MapProblem::New(self.mapPath, firstInFile, format!("area of {} tile(s) can't be reached from the player start", region.len())));
+        }
+    }
+
+    fn FloodFill(&self, start: iPoint2, visited: &mut Array2<bool>) -> Vec<iPoint2> {
+        let mut region: Vec<iPoint2> = Vec::new();
+        let mut queue: VecDeque<iPoint2> = VecDeque::from([start]);
+        visited[(start.y() as usize, start.x() as usize)] = true;
+
+        while let Some(tileCoord) = queue.pop_front() {
+            region.push(tileCoord);
+            let (x, y) = (tileCoord.x(), tileCoord.y());
+            for neighbour in [iPoint2::New(x+1, y), iPoint2::New(x-1, y), iPoint2::New(x, y+1), iPoint2::New(x, y-1)] {
+                if self.map.WithinMap(neighbour) && !visited[(neighbour.y() as usize, neighbour.x() as usize)] && self.IsWalkable(neighbour) {
+                    visited[(neighbour.y() as usize, neighbour.x() as usize)] = true;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        region
+    }
+}
@@ -7,11 +7,15 @@ use sdl2::{
 };
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, TextureCreator, WindowCanvas};
+use sdl2::render::{BlendMode, Texture, TextureCreator, WindowCanvas};
 use sdl2::surface::{Surface};
-use sdl2::video::WindowContext;
+use sdl2::video::{FullscreenType, WindowContext};
+use crate::lighting::Shade;
+use crate::load_error::LoadError;
+use crate::framebuffer::SoftwareTextures;
+use crate::renderer::Renderer;
 use crate::tiles::TextureHandle;
-use crate::utils::conventions::TEXTURE_PITCH;
+use crate::utils::bmp::Bitmap;
 use crate::utils::dda::RayCursor;
 use crate::utils::misc_math::DegreesToRadians;
 use crate::utils::dda::wallType_t;
@@ -23,13 +27,15 @@ pub struct Multimedia {
     pub sdlTextureCreator: TextureCreator<WindowContext>,
     pub displayParams: DisplayParams,
     pub windowParams: WindowParams,
-    pub renderParams: RenderParams,
     pub assets: Assets,
+    streamingTexture: Option<Texture>,
 }
 
 impl Multimedia {
-    pub fn New(windowWidth: usize, windowHeight: usize, fov: f64) -> Self {
+    pub fn New(windowWidth: usize, windowHeight: usize, softwareTextures: &SoftwareTextures) -> Result<Self, LoadError> {
         let sdlContexts = SDLContexts::New();
+        // Nearest-neighbour scaling, so low internal render resolutions upscale into crisp chunky pixels
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
         let displayMode = sdlContexts.sdlVideoSubsystem.current_display_mode(0).unwrap();
         let sdlEventPump = sdlContexts.sdlContext.event_pump().unwrap();
         let sdlCanvas = sdlContexts
@@ -46,21 +52,36 @@ impl Multimedia {
             refreshRate: displayMode.refresh_rate as usize
         };
         let windowParams = WindowParams{width: windowWidth, height: windowHeight};
-        let renderParams = RenderParams::New(fov, windowWidth);
-        let assets = Assets::New(&sdlTextureCreator);
+        let assets = Assets::New(&sdlTextureCreator, softwareTextures);
 
         sdlContexts.sdlContext.mouse().set_relative_mouse_mode(true);
 
-        return Self {
+        return Ok(Self {
             sdlContexts,
             sdlEventPump,
             sdlCanvas,
             sdlTextureCreator,
             displayParams,
             windowParams,
-            renderParams,
             assets,
-        }
+            streamingTexture: None,
+        })
+    }
+
+    pub fn ToggleFullscreen(&mut self) {
+        let window = self.sdlCanvas.window_mut();
+        let fullscreenType = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off
+        };
+        window.set_fullscreen(fullscreenType).unwrap();
+    }
+
+    // Re-reads the drawable size after the window was resized or went (un)fullscreen
+    pub fn UpdateWindowParams(&mut self) -> WindowParams {
+        let (width, height) = self.sdlCanvas.output_size().unwrap();
+        self.windowParams = WindowParams{width: width as usize, height: height as usize};
+        self.windowParams
     }
 }
 
@@ -88,6 +109,7 @@ impl SDLContexts {
         let _sdlWindow = self.sdlVideoSubsystem
             .window(title, width, height)
             .position_centered()
+            .resizable()
             .build()
             .unwrap();
 
@@ -101,6 +123,7 @@ pub struct DisplayParams {
     pub refreshRate: usize
 }
 
+#[derive(Clone, Copy)]
 pub struct WindowParams {
     pub width: usize,
     pub height: usize,
@@ -108,29 +131,37 @@ pub struct WindowParams {
 
 pub struct RenderParams {
     pub fov: f64,
+    pub pixelAspect: f64,
     pub projPlaneDist: f64,
     pub castingRayAngles: Vec<(f64, f64)>,
     pub renderHeightProprConst: f64
 }
 
+// How tall a wall is on screen relative to how wide a tile is ; walls in the original are slightly taller than wide
+const WALL_HEIGHT_SCALE: f64 = 1.035;
+
 impl RenderParams {
-    pub fn New(fov: f64, windowWidth: usize) -> Self {
+    // fov is horizontal ; the vertical field of view follows from the frame's aspect ratio. pixelAspect is the
+    // on-screen height/width of one frame pixel, 1.0 unless the frame gets stretched to a differently shaped window.
+    pub fn New(fov: f64, windowWidth: usize, pixelAspect: f64) -> Self {
         // Calculate casting ray angles
         let mut castingRayAngles: Vec<(f64, f64)> = vec![(0.0, 0.0); windowWidth];
         let projectionPlaneWidth: f64 = 2.0 * DegreesToRadians(fov / 2.0).tan();
         let segmentLength: f64 = projectionPlaneWidth / windowWidth as f64;
-        for x in 0..windowWidth - 1 {
+        for x in 0..windowWidth {
             let currAngle = (-(x as f64 * segmentLength - (projectionPlaneWidth / 2.0))).atan();
             castingRayAngles[x] = (currAngle, currAngle.cos());
         }
 
         let projPlaneDist = (windowWidth as f64 / 2.0) / DegreesToRadians(fov / 2.0).tan();
 
-        // Render height proportionality constant ; takes into account screen aspect ratio
-        let renderHeightProprConst = 1.15 * (windowWidth as f64) / ((16.0 / 9.0) * (fov / 72.0));
+        // Render height proportionality constant ; a wall at perpendicular distance d is renderHeightProprConst/d pixels tall.
+        // Uses the same projection as the horizontal axis so geometry keeps its proportions for any window shape.
+        let renderHeightProprConst = WALL_HEIGHT_SCALE * projPlaneDist / pixelAspect;
 
         RenderParams {
             fov,
+            pixelAspect,
             projPlaneDist,
             castingRayAngles,
             renderHeightProprConst
@@ -143,26 +174,14 @@ pub struct Assets {
 }
 
 impl Assets {
-    pub fn New(sdlTextureCreator: &TextureCreator<WindowContext>) -> Self {
+    // Uploads the already loaded CPU-side textures, so both renderers draw from the same source
+    pub fn New(sdlTextureCreator: &TextureCreator<WindowContext>, softwareTextures: &SoftwareTextures) -> Self {
 
         // Texture sheets
-        let wall_TS    = TextureSheet::New(sdlTextureCreator,  "assets/wall_textures.bmp", 6, 110, false);
-        let object_TS  = TextureSheet::New(sdlTextureCreator,  "assets/objects.bmp", 5, 50, true);
-        let guard_TS   = TextureSheet::New(sdlTextureCreator,  "assets/guard.bmp", 8, 51, true);
-        let officer_TS = TextureSheet::New(sdlTextureCreator,  "assets/officer.bmp", 8, 51, true);
-        let SS_TS      = TextureSheet::New(sdlTextureCreator,  "assets/SS.bmp", 8, 51, true);
-        let weapons_TS = TextureSheet::New(sdlTextureCreator,  "assets/weapons.bmp", 5, 20, true);
-
         let mut textureSheets: HashMap<TextureType, TextureSheet> = HashMap::new();
-            textureSheets.insert(TextureType::WALL, wall_TS);
-            textureSheets.insert(TextureType::OBJECT, object_TS);
-            textureSheets.insert(TextureType::GUARD, guard_TS);
-            textureSheets.insert(TextureType::OFFICER, officer_TS);
-            textureSheets.insert(TextureType::SS, SS_TS);
-            textureSheets.insert(TextureType::WEAPON, weapons_TS);
-
-        // Animation texture reel sets
-
+        for spec in &TEXTURE_SHEET_SPECS {
+            textureSheets.insert(spec.textureType, TextureSheet::New(sdlTextureCreator, softwareTextures.GetSheet(spec.textureType)));
+        }
 
         Self {
             textureSheets
@@ -173,9 +192,17 @@ impl Assets {
         &self.textureSheets[&textureHandle.textureType].textures[(textureHandle.ID-1) as usize]
     }
 
+    pub fn GetMutTexture(&mut self, textureHandle: TextureHandle) -> &mut Texture {
+        &mut self.textureSheets.get_mut(&textureHandle.textureType).unwrap().textures[(textureHandle.ID-1) as usize]
+    }
+
+    pub fn GetMutSilhouette(&mut self, textureHandle: TextureHandle) -> &mut Texture {
+        &mut self.textureSheets.get_mut(&textureHandle.textureType).unwrap().silhouettes[(textureHandle.ID-1) as usize]
+    }
+
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum TextureType {
     WALL,
     OBJECT,
@@ -185,41 +212,136 @@ pub enum TextureType {
     WEAPON
 }
 
+// Layout of each texture sheet BMP ; shared by the SDL assets and the software framebuffer
+pub struct TextureSheetSpec {
+    pub textureType: TextureType,
+    pub filename: &'static str,
+    pub sheetPitch: i32,
+    pub numTextures: i32,
+    pub colorKey: bool
+}
+
+pub const TEXTURE_SHEET_SPECS: [TextureSheetSpec; 6] = [
+    TextureSheetSpec { textureType: TextureType::WALL,    filename: "assets/wall_textures.bmp", sheetPitch: 6, numTextures: 110, colorKey: false },
+    TextureSheetSpec { textureType: TextureType::OBJECT,  filename: "assets/objects.bmp",       sheetPitch: 5, numTextures: 50,  colorKey: true },
+    TextureSheetSpec { textureType: TextureType::GUARD,   filename: "assets/guard.bmp",         sheetPitch: 8, numTextures: 51,  colorKey: true },
+    TextureSheetSpec { textureType: TextureType::OFFICER, filename: "assets/officer.bmp",       sheetPitch: 8, numTextures: 51,  colorKey: true },
+    TextureSheetSpec { textureType: TextureType::SS,      filename: "assets/SS.bmp",            sheetPitch: 8, numTextures: 51,  colorKey: true },
+    TextureSheetSpec { textureType: TextureType::WEAPON,  filename: "assets/weapons.bmp",       sheetPitch: 5, numTextures: 20,  colorKey: true },
+];
+
+pub const COLOR_KEY: pixels::Color = pixels::Color{r: 152, g: 0, b: 136, a: 255};
+
+// Where textures are read from ; the BMP sheets above, or the original game's VSWAP file and its palette
+#[derive(Clone, Default, Debug)]
+pub enum TextureSource {
+    #[default]
+    SHEETS,
+    VSWAP {
+        vswapPath: String,
+        palettePath: String
+    }
+}
+
 struct TextureSheet {
-    filename: String,
-    pitch: i32,
-    pub textures: Vec<Texture>
+    pub textures: Vec<Texture>,
+
+    // White, additively blended copies of each texture's opaque texels ; used to blend fog into shaded copies
+    pub silhouettes: Vec<Texture>
 }
 
 impl TextureSheet {
-    fn New(sdlTextureCreator: &TextureCreator<WindowContext>, filename: &str, sheetPitch: i32, numTextures: i32, colorKey: bool) -> Self {
+    fn New(sdlTextureCreator: &TextureCreator<WindowContext>, bitmaps: &[Bitmap]) -> Self {
         let mut textures: Vec<Texture> = Vec::new();
-        let mut sheetSurface = Surface::load_bmp(filename).unwrap();
+        let mut silhouettes: Vec<Texture> = Vec::new();
 
-        if colorKey {
-            let _ = sheetSurface.set_color_key(true, pixels::Color{r: 152, g: 0, b: 136, a: 255}).unwrap();
-        }
+        for bitmap in bitmaps {
+            // ARGB8888 is stored B,G,R,A in memory ; the silhouette keeps alpha and whitens everything else
+            let mut textureSurface = Surface::new(bitmap.width as u32, bitmap.height as u32, PixelFormatEnum::ARGB8888).unwrap();
+            let mut silhouetteSurface = Surface::new(bitmap.width as u32, bitmap.height as u32, PixelFormatEnum::ARGB8888).unwrap();
+            let pitch = textureSurface.pitch() as usize;
+            textureSurface.with_lock_mut(|texturePixels| {
+                silhouetteSurface.with_lock_mut(|silhouettePixels| {
+                    for row in 0..bitmap.height {
+                        for col in 0..bitmap.width {
+                            let [r, g, b, a] = bitmap.GetPixel(col, row);
+                            let i = row*pitch + col*4;
+                            texturePixels[i..i+4].copy_from_slice(&[b, g, r, a]);
+                            silhouettePixels[i..i+4].copy_from_slice(&[255, 255, 255, a]);
+                        }
+                    }
+                });
+            });
 
-        for textureID in 1..=numTextures {
-            textures.push ({
-                let textureX = ((textureID - 1) % sheetPitch) * TEXTURE_PITCH as i32;
-                let textureY = ((textureID - 1) / sheetPitch) * TEXTURE_PITCH as i32;
-        
-                let mut extractedTextureSurface = Surface::new(TEXTURE_PITCH, TEXTURE_PITCH, PixelFormatEnum::ARGB8888).unwrap();
-                let _ = sheetSurface.blit(Rect::new(textureX, textureY, TEXTURE_PITCH, TEXTURE_PITCH), &mut extractedTextureSurface, Rect::new(0, 0, TEXTURE_PITCH, TEXTURE_PITCH));
-        
-                sdlTextureCreator.create_texture_from_surface(&extractedTextureSurface).unwrap()
-        });
+            let mut texture = sdlTextureCreator.create_texture_from_surface(&textureSurface).unwrap();
+            texture.set_blend_mode(BlendMode::Blend);
+            textures.push(texture);
+
+            let mut silhouette = sdlTextureCreator.create_texture_from_surface(&silhouetteSurface).unwrap();
+            silhouette.set_blend_mode(BlendMode::Add);
+            silhouettes.push(silhouette);
         }
-        
+
         Self {
-            filename: filename.to_string(),
-            pitch: sheetPitch,
-            textures
+            textures,
+            silhouettes
         }
     }
 }
 
+impl Renderer for Multimedia {
+    fn Clear(&mut self) {
+        self.sdlCanvas.clear();
+    }
+
+    fn FillRect(&mut self, color: pixels::Color, rect: Rect) {
+        self.sdlCanvas.set_draw_color(color);
+        self.sdlCanvas.fill_rect(rect).unwrap();
+    }
+
+    // Shading is done in two passes: the texture darkened by color mod, then (if fogged) its
+    // silhouette tinted with the fog color and added on top with alpha = fog amount
+    fn CopyTexture(&mut self, textureHandle: TextureHandle, srcRect: Rect, dstRect: Rect, shade: Shade) {
+        let litMod = (shade.brightness*(1.0 - shade.fogAmount)*255.0).round().clamp(0.0, 255.0) as u8;
+        let texture = self.assets.GetMutTexture(textureHandle);
+        texture.set_color_mod(litMod, litMod, litMod);
+        let _ = self.sdlCanvas.copy(texture, srcRect, dstRect);
+
+        if shade.fogAmount > 0.0 {
+            let silhouette = self.assets.GetMutSilhouette(textureHandle);
+            silhouette.set_color_mod(shade.fogColor.r, shade.fogColor.g, shade.fogColor.b);
+            silhouette.set_alpha_mod((shade.fogAmount*255.0).round().clamp(0.0, 255.0) as u8);
+            let _ = self.sdlCanvas.copy(silhouette, srcRect, dstRect);
+        }
+    }
+
+    fn DrawBitmap(&mut self, bitmap: &Bitmap, dstRect: Rect) {
+        // (Re)create the streaming texture only when the bitmap size changes
+        let needsNewTexture = match &self.streamingTexture {
+            Some(texture) => {
+                let query = texture.query();
+                query.width != bitmap.width as u32 || query.height != bitmap.height as u32
+            },
+            None => true
+        };
+        if needsNewTexture {
+            if let Some(oldTexture) = self.streamingTexture.take() {
+                // With unsafe_textures, textures are not freed on drop
+                unsafe { oldTexture.destroy(); }
+            }
+            self.streamingTexture = Some(self.sdlTextureCreator.create_texture_streaming(PixelFormatEnum::ABGR8888, bitmap.width as u32, bitmap.height as u32).unwrap());
+        }
+
+        let texture = self.streamingTexture.as_mut().unwrap();
+        texture.update(None, bitmap.pixels.as_flattened(), bitmap.width*4).unwrap();
+        let _ = self.sdlCanvas.copy(texture, None, dstRect);
+    }
+
+    fn Present(&mut self) {
+        self.sdlCanvas.present();
+    }
+}
+
 pub fn LightTexture(rayCursor: &mut RayCursor, litTexture: TextureHandle, unlitTexture: TextureHandle) -> TextureHandle {
     match rayCursor.GetWallType() {
         wallType_t::HORIZONTAL => unlitTexture,
@@ -0,0 +1,101 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use crate::map::Map;
+use crate::tiles::Tile;
+use crate::utils::vec2d::iPoint2;
+
+/*
+=========================================================
+    Enemy pathfinding
+
+    A* over the map's tile grid, moving in 8 directions. Walls, solid
+    objects and tiles flagged `B` are blocked ; doors count as open, since
+    enemies can get through them. A diagonal step is only taken when both
+    tiles it passes between are open too, so paths never clip a corner.
+    Costs are whole numbers (10 straight, 14 diagonal) and ties are broken
+    by the order tiles were reached, so the same map always gives the same
+    path.
+=========================================================
+*/
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+const NEIGHBOUR_OFFSETS: [(i32, i32); 8] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// Tiles from `from` (left out) to `to` (included) ; None if `to` can't be reached. The end tiles themselves are
+// never checked, so an enemy standing somewhere odd can still find its way out, and the player's tile can be the goal
+pub fn FindPath(map: &Map, from: iPoint2, to: iPoint2) -> Option<Vec<iPoint2>> {
+    let withinGrid = |tile: iPoint2| tile.x() >= 0 && tile.x() < map.width && tile.y() >= 0 && tile.y() < map.height;
+    if !withinGrid(from) || !withinGrid(to) {
+        return None;
+    }
+
+    // Indexed [x][y], like the map's own tiles
+    let mut costs: Vec<Vec<u32>> = vec![vec![u32::MAX; map.height as usize]; map.width as usize];
+    let mut cameFrom: Vec<Vec<Option<iPoint2>>> = vec![vec![None; map.height as usize]; map.width as usize];
+    let mut openTiles: BinaryHeap<Reverse<(u32, u32, i32, i32)>> = BinaryHeap::new();
+    let mut numReached: u32 = 0;
+
+    costs[from.x() as usize][from.y() as usize] = 0;
+    openTiles.push(Reverse((EstimateCost(from, to), numReached, from.x(), from.y())));
+
+    while let Some(Reverse((estimate, _, x, y))) = openTiles.pop() {
+        let tile = iPoint2::New(x, y);
+        let cost = costs[x as usize][y as usize];
+        if tile == to {
+            let mut path = vec![to];
+            while let Some(previous) = cameFrom[path.last().unwrap().x() as usize][path.last().unwrap().y() as usize] {
+                path.push(previous);
+            }
+            path.pop();
+            path.reverse();
+            return Some(path);
+        }
+        // Reached again more cheaply since this entry was queued
+        if cost + EstimateCost(tile, to) < estimate {
+            continue;
+        }
+
+        for (dx, dy) in NEIGHBOUR_OFFSETS {
+            let neighbour = iPoint2::New(x + dx, y + dy);
+            let isDiagonal = dx != 0 && dy != 0;
+            if !withinGrid(neighbour) || (neighbour != to && !EnemyPassable(map, neighbour)) {
+                continue;
+            }
+            if isDiagonal && !(EnemyPassable(map, iPoint2::New(x + dx, y)) && EnemyPassable(map, iPoint2::New(x, y + dy))) {
+                continue;
+            }
+
+            let neighbourCost = cost + if isDiagonal { DIAGONAL_COST } else { STRAIGHT_COST };
+            if neighbourCost < costs[neighbour.x() as usize][neighbour.y() as usize] {
+                costs[neighbour.x() as usize][neighbour.y() as usize] = neighbourCost;
+                cameFrom[neighbour.x() as usize][neighbour.y() as usize] = Some(tile);
+                numReached += 1;
+                openTiles.push(Reverse((neighbourCost + EstimateCost(neighbour, to), numReached, neighbour.x(), neighbour.y())));
+            }
+        }
+    }
+
+    None
+}
+
+// Whether an enemy could ever walk through the tile ; unlike Map::ValidEnemyLocation, closed doors count
+pub fn EnemyPassable(map: &Map, tileCoord: iPoint2) -> bool {
+    if !map.WithinMap(tileCoord) || map.GetFlags(tileCoord).blocksEnemies {
+        return false;
+    }
+    match map.GetTile(tileCoord) {
+        Tile::EMPTY(_) | Tile::DOOR(_) => true,
+        Tile::OBJECT(object) => !object.PlayerTileHit(),
+        Tile::NONE => panic!(),
+        _ => false
+    }
+}
+
+// Octile distance ; never more than the real cost, so paths come out shortest
+fn EstimateCost(from: iPoint2, to: iPoint2) -> u32 {
+    let dx = (to.x() - from.x()).unsigned_abs();
+    let dy = (to.y() - from.y()).unsigned_abs();
+    STRAIGHT_COST*dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST)*dx.min(dy)
+}
@@ -0,0 +1,100 @@
+/*
+=========================================================
+    Pathfinding tests
+
+    Paths worked out on a small snake shaped map, with the tile in the
+    middle of its one route swapped for a door, a wall, objects and flags,
+    then a guard chasing the player all the way round it.
+=========================================================
+*/
+
+use rand::{rngs::StdRng, SeedableRng};
+use crate::game_state::{GameState, TickInputs};
+use crate::map::Map;
+use crate::pathfinding::FindPath;
+use crate::utils::scratch::WriteScratchFile;
+use crate::utils::vec2d::{iPoint2, Vec2};
+
+const SCRATCH_DIR: &str = "target/pathfinding_tests";
+
+// The only way from one end to the other is round the west end of the middle wall ; (3, 1) is changed by the tests
+const SNAKE: &str = "\
+[map]
+name = Snake
+width = 7
+height = 5
+
+[walls]
+15,15,15,15,15,15,15
+15,,,,,,15
+15,,15,15,15,15,15
+15,,,,,,15
+15,15,15,15,15,15,15
+
+[objects]
+,,,,,,
+,,,,,,
+,,,,,,
+,,,OBJECT,,,
+,,,,,,
+
+[actors]
+,,,,,,
+,,,,,GU,
+,,,,,,
+,,,,,P-N,
+,,,,,,
+
+[flags]
+,,,,,,
+,,,,,,
+,,,,,,
+,,,FLAGS,,,
+,,,,,,
+";
+
+fn WriteSnake(fileName: &str, walls: &str, object: &str, flags: &str) -> String {
+    let contents = SNAKE
+        .replace("15,,,,,,15\n15,15", &format!("15,,,{},,,15\n15,15", walls))
+        .replace("OBJECT", object)
+        .replace("FLAGS", flags);
+    WriteScratchFile(SCRATCH_DIR, fileName, contents)
+}
+
+fn SnakePath(fileName: &str, walls: &str, object: &str, flags: &str) -> Option<Vec<iPoint2>> {
+    let (map, _) = Map::Load(&WriteSnake(fileName, walls, object, flags), &mut StdRng::seed_from_u64(0)).unwrap();
+    FindPath(&map, iPoint2::New(5, 3), iPoint2::New(5, 1))
+}
+
+#[test]
+fn path_goes_round_walls_without_cutting_corners() {
+    let path = SnakePath("open.wmap", "", "", "").unwrap();
+    let expected: Vec<iPoint2> = [(4, 3), (3, 3), (2, 3), (1, 3), (1, 2), (1, 1), (2, 1), (3, 1), (4, 1), (5, 1)]
+        .iter().map(|&(x, y)| iPoint2::New(x, y)).collect();
+    assert_eq!(path, expected);
+}
+
+#[test]
+fn doors_and_see_through_objects_let_paths_through() {
+    assert!(SnakePath("door.wmap", "D", "", "").is_some());
+    assert!(SnakePath("passable_object.wmap", "", "3", "").is_some());
+
+    assert_eq!(SnakePath("wall.wmap", "15", "", ""), None);
+    assert_eq!(SnakePath("solid_object.wmap", "", "4", ""), None);
+    assert_eq!(SnakePath("flagged.wmap", "", "", "B"), None);
+}
+
+#[test]
+fn chasing_enemy_follows_the_path_to_the_player() {
+    let mut gameState = GameState::New(&WriteSnake("chase.wmap", "", "", ""), 0, false).unwrap();
+    gameState.player.SetViewDir(Vec2::New(-1.0, 0.0));
+    gameState.enemies[0].inputsBuffer.damage = true;
+
+    for _ in 0..900 {
+        gameState.Step(&TickInputs::default());
+    }
+
+    let guardTile = gameState.enemies[0].tile;
+    assert_eq!(guardTile.y(), 1, "guard should have come round to the player's row, ended at {:?}", guardTile);
+    assert!(gameState.player.health < 100, "guard should have caught up and shot the player");
+}
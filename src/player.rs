@@ -1,30 +1,67 @@
 use std::f64::consts::PI;
 use crate::animation::{AnimationClip, AnimationMagazine, AnimationReel};
 use crate::enemy::Enemy;
-use crate::inputs_buffer::{InputsBuffer, lookCommand_t, moveCommand_t};
+use crate::game_state::{TickInputs, moveCommand_t};
 use crate::map::Map;
 use crate::tiles::{Tile, DoorStatus, TextureHandle};
 use crate::utils::dda::RayCursor;
 use crate::utils::ray::Ray;
-use crate::utils::vec2d::Vec2;
+use crate::utils::vec2d::{iPoint2, Vec2};
 use super::utils::vec2d::Point2;
-use crate::inputs_buffer::doorCommand_t;
 use crate::multimedia::TextureType;
 
+// Furthest the horizon can be sheared up or down, as a fraction of the window height
+const MAX_PITCH: f64 = 0.35;
+
+const PLAYER_MAX_HEALTH: i32 = 100;
+
+// Where the player stands and looks ; kept from the previous tick so frames between ticks can be interpolated
+#[derive(Copy, Clone)]
+pub struct PlayerPose {
+    pub location: Point2,
+    pub viewDir: Vec2,
+    pub pitch: f64
+}
+
+impl PlayerPose {
+    pub fn Lerp(from: &PlayerPose, to: &PlayerPose, alpha: f64) -> PlayerPose {
+        PlayerPose {
+            location: from.location + (to.location - from.location)*alpha,
+            viewDir: (from.viewDir + (to.viewDir - from.viewDir)*alpha).UnitVector(),
+            pitch: from.pitch + (to.pitch - from.pitch)*alpha
+        }
+    }
+
+    // Right of the view direction, as Player::east
+    pub fn East(&self) -> Vec2 {
+        self.viewDir.Rotate(-PI/2.0)
+    }
+}
+
+// How far the player moves per tick, and turns and looks up or down per mouse count
+#[derive(Copy, Clone)]
+pub struct PlayerSpeeds {
+    pub moveIncr: f64,
+    pub swivelIncr: f64,
+    pub pitchIncr: f64
+}
+
 pub struct Player {
     pub location: Point2,
     pub viewDir: Vec2,
+    pub pitch: f64,
     pub east: Vec2,
     pub west: Vec2,
+    pub health: i32,
+
+    // Points for enemies killed
+    pub score: u32,
 
     pub AM_weapon: AnimationMagazine
 }
 
 impl Player {
-    pub fn New(location: Point2, refreshRate: usize) -> Self {
-        let viewDir = Point2::New(-0.46290, 0.88640).UnitVector();
-        let east = viewDir.Rotate(-PI/2.0);
-        let west = viewDir.Rotate(PI/2.0);
+    pub fn New(location: Point2, viewDir: Vec2) -> Self {
 
         let AM_pistol = AnimationMagazine::New(vec![
             AnimationClip::STATIC(
@@ -39,25 +76,51 @@ impl Player {
                         TextureHandle::New(TextureType::WEAPON, 9)
                     ],
                     0.15,
-                    0.05*(60.0/(refreshRate as f64)),
+                    0.05,
                     Some(0))
             )
         ], 0);
 
-        Player {
+        let mut player = Player {
             location,
-            viewDir,
-            east,
-            west,
+            viewDir: Vec2::default(),
+            pitch: 0.0,
+            east: Vec2::default(),
+            west: Vec2::default(),
+            health: PLAYER_MAX_HEALTH,
+            score: 0,
             AM_weapon: AM_pistol
+        };
+        player.SetViewDir(viewDir);
+
+        player
+    }
+
+    pub fn GetPose(&self) -> PlayerPose {
+        PlayerPose {
+            location: self.location,
+            viewDir: self.viewDir,
+            pitch: self.pitch
         }
     }
 
-    pub fn Update(&mut self, inputsBuffer: &InputsBuffer, map: &mut Map, enemies: &mut Vec<Enemy>, moveIncr: f64, swivelIncr: f64) {
+    pub fn SetViewDir(&mut self, viewDir: Vec2) {
+        self.viewDir = viewDir.UnitVector();
+        self.east = self.viewDir.Rotate(-PI/2.0);
+        self.west = self.viewDir.Rotate(PI/2.0);
+    }
+
+    // Health bottoms out at 0 ; nothing ends the game yet when it gets there
+    pub fn TakeDamage(&mut self, damage: i32) {
+        self.health = (self.health - damage).max(0);
+    }
+
+    pub fn Update(&mut self, inputs: &TickInputs, verticalLook: bool, map: &mut Map, enemies: &mut Vec<Enemy>, speeds: &PlayerSpeeds) {
+        let PlayerSpeeds { moveIncr, swivelIncr, pitchIncr } = *speeds;
 
         let mut proposedLoc: Point2 = self.location;
 
-        match inputsBuffer.moveCommand {
+        match inputs.moveCommand {
             moveCommand_t::NORTH => { proposedLoc = self.location + self.viewDir*moveIncr; }
             moveCommand_t::SOUTH => { proposedLoc = self.location - self.viewDir*moveIncr; }
             moveCommand_t::EAST => { proposedLoc = self.location + self.east*moveIncr; }
@@ -68,50 +131,65 @@ impl Player {
         }
         self.MoveIfValid(proposedLoc, map);
 
-        match inputsBuffer.lookCommand {
-            lookCommand_t::RIGHT => { self.viewDir = self.viewDir.Rotate(-swivelIncr*inputsBuffer.mouseAbsXrel as f64); }
-            lookCommand_t::LEFT => { self.viewDir = self.viewDir.Rotate(swivelIncr*inputsBuffer.mouseAbsXrel as f64); }
-            lookCommand_t::NONE => {}
+        // Moving the mouse right turns clockwise
+        if inputs.mouseXrel != 0 {
+            self.viewDir = self.viewDir.Rotate(-swivelIncr*inputs.mouseXrel as f64);
         }
 
         self.east = self.viewDir.Rotate(-PI/2.0);
         self.west = self.viewDir.Rotate(PI/2.0);
 
-        match inputsBuffer.doorCommand {
-            doorCommand_t::OPEN => {
-                let mut rayCursor = RayCursor::New(Ray::New(self.location, self.viewDir), self.location);
-                while map.WithinMap(rayCursor.hitTile) {
-                    rayCursor.GoToNextHit();
-                    if rayCursor.GetDistToHitPoint() > 4.0 {
-                        break;
-                    } else {
-                        match map.GetMutTile(rayCursor.hitTile) {
-                            Tile::EMPTY(_) => {
-                                continue;
-                            },
-                            Tile::DOOR(hitDoor) => {
-                                if hitDoor.status == DoorStatus::CLOSED || hitDoor.status == DoorStatus::CLOSING {
-                                    (*hitDoor).status = DoorStatus::OPENING;
-                                    break;
-                                } else if hitDoor.status == DoorStatus::OPENING {
-                                    break;
-                                }
-                            },
-                            Tile::NONE => panic!(),
-                            _ => {
+        // Moving the mouse down looks down, i.e. raises the horizon
+        if verticalLook {
+            self.pitch = (self.pitch - pitchIncr*inputs.mouseYrel as f64).clamp(-MAX_PITCH, MAX_PITCH);
+        } else {
+            self.pitch = 0.0;
+        }
+
+        if inputs.openDoor {
+            let mut rayCursor = RayCursor::New(Ray::New(self.location, self.viewDir), self.location);
+            while map.WithinMap(rayCursor.hitTile) {
+                rayCursor.GoToNextHit();
+                if rayCursor.GetDistToHitPoint() > 4.0 {
+                    break;
+                } else {
+                    match map.GetMutTile(rayCursor.hitTile) {
+                        Tile::EMPTY(_) => {
+                            continue;
+                        },
+                        Tile::DOOR(hitDoor) => {
+                            if hitDoor.status == DoorStatus::CLOSED || hitDoor.status == DoorStatus::CLOSING {
+                                (*hitDoor).status = DoorStatus::OPENING;
+                                break;
+                            } else if hitDoor.status == DoorStatus::OPENING {
                                 break;
                             }
+                        },
+                        Tile::NONE => panic!(),
+                        _ => {
+                            break;
                         }
                     }
                 }
-            },
-            _ => {}
+            }
         }
 
         self.AM_weapon.Update();
-        if inputsBuffer.fireWeapon {
+        if inputs.fireWeapon {
+            // Holding fire keeps the weapon cycling ; a new shot only goes off once it's back at rest
+            let shotFired = self.AM_weapon.currClipIndex == 0;
             self.AM_weapon.currClipIndex = 1;
 
+            // The shot is heard by every enemy in an area joined to the player's through doors that aren't shut
+            if shotFired {
+                let heard = map.AreasInEarshot(iPoint2::from(self.location));
+                for e in enemies.iter_mut() {
+                    if map.GetAreas(e.tile).iter().any(|&area| heard[area]) {
+                        e.inputsBuffer.noise = Some(self.location);
+                    }
+                }
+            }
+
             let mut rayCursor = RayCursor::New(Ray::New(self.location, self.viewDir), self.location);
     'outer: while map.WithinMap(rayCursor.hitTile) {
                 rayCursor.GoToNextHit();
@@ -144,6 +222,15 @@ impl Player {
                         }
                     },
                     Tile::DOOR(door) => {
+                        // Enemies only get into a doorway once the gate is nearly open, so nothing shields them there
+                        if !door.enemySprites.is_empty() {
+                            for e in &mut *enemies {
+                                if e.tile == rayCursor.hitTile && e.health > 0 {
+                                    e.inputsBuffer.damage = true;
+                                    break 'outer;
+                                }
+                            }
+                        }
                         if door.GetWallSlice(&mut rayCursor).is_none() {
                             continue;
                         } else {
@@ -0,0 +1,210 @@
+use std::error::Error;
+use std::fs::File;
+use std::time::{Duration, Instant};
+use sdl2::{pixels::Color, rect::Rect};
+use crate::raycaster::RenderStats;
+use crate::renderer::Renderer;
+use crate::utils::font::{GetGlyph, GLYPH_WIDTH, GLYPH_HEIGHT};
+
+/*
+=========================================================
+    FrameSample struct definition
+=========================================================
+*/
+
+// One frame's timings in milliseconds, plus the raycaster's work counters
+#[derive(Default, Clone, Copy)]
+struct FrameSample {
+    frame: f64,
+    update: f64,
+    drawCeilingAndFloor: f64,
+    renderIntoBuffers: f64,
+    drawWallsFromBuffer: f64,
+    drawSpritesFromBuffer: f64,
+    drawWeapon: f64,
+    raySteps: f64,
+    spritesDrawn: f64
+}
+
+impl FrameSample {
+    fn New(frameTime: Duration, updateTime: Duration, renderStats: &RenderStats) -> Self {
+        let ms = |d: Duration| d.as_secs_f64()*1000.0;
+        Self {
+            frame: ms(frameTime),
+            update: ms(updateTime),
+            drawCeilingAndFloor: ms(renderStats.drawCeilingAndFloor),
+            renderIntoBuffers: ms(renderStats.renderIntoBuffers),
+            drawWallsFromBuffer: ms(renderStats.drawWallsFromBuffer),
+            drawSpritesFromBuffer: ms(renderStats.drawSpritesFromBuffer),
+            drawWeapon: ms(renderStats.drawWeapon),
+            raySteps: renderStats.raySteps as f64,
+            spritesDrawn: renderStats.spritesDrawn as f64
+        }
+    }
+
+    // Exponential moving average towards sample, so overlay numbers stay readable
+    fn Blend(&mut self, sample: &FrameSample, weight: f64) {
+        let mix = |avg: &mut f64, value: f64| *avg += (value - *avg)*weight;
+        mix(&mut self.frame, sample.frame);
+        mix(&mut self.update, sample.update);
+        mix(&mut self.drawCeilingAndFloor, sample.drawCeilingAndFloor);
+        mix(&mut self.renderIntoBuffers, sample.renderIntoBuffers);
+        mix(&mut self.drawWallsFromBuffer, sample.drawWallsFromBuffer);
+        mix(&mut self.drawSpritesFromBuffer, sample.drawSpritesFromBuffer);
+        mix(&mut self.drawWeapon, sample.drawWeapon);
+        mix(&mut self.raySteps, sample.raySteps);
+        mix(&mut self.spritesDrawn, sample.spritesDrawn);
+    }
+}
+
+/*
+=========================================================
+    Profiler struct definition
+=========================================================
+*/
+
+const AVERAGE_WEIGHT: f64 = 0.1;
+const OVERLAY_SCALE: i32 = 2;
+const OVERLAY_MARGIN: i32 = 4;
+const OVERLAY_LINE_HEIGHT: i32 = (GLYPH_HEIGHT + 2)*OVERLAY_SCALE;
+const OVERLAY_TEXT_COLOR: Color = Color::RGBA(255, 255, 0, 255);
+const OVERLAY_BACKGROUND_COLOR: Color = Color::RGBA(0, 0, 0, 255);
+
+// Frame timing: an on-screen overlay of smoothed FPS/stage times, and optionally every frame's raw numbers written to a CSV file
+pub struct Profiler {
+    pub showOverlay: bool,
+    csvPath: String,
+    csvWriter: Option<csv::Writer<File>>,
+    frameIndex: usize,
+    lastFrameEnd: Option<Instant>,
+    updateStart: Instant,
+    updateTime: Duration,
+    averaged: FrameSample
+}
+
+impl Profiler {
+    pub fn New(showOverlay: bool, csvPath: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let csvWriter = match csvPath {
+            Some(path) => {
+                let CSVError = |e: csv::Error| format!("{}: {}", path, e);
+                let mut writer = csv::Writer::from_path(path).map_err(CSVError)?;
+                writer.write_record([
+                    "frame", "frame_ms", "update_ms", "draw_ceiling_and_floor_ms", "render_into_buffers_ms",
+                    "draw_walls_from_buffer_ms", "draw_sprites_from_buffer_ms", "draw_weapon_ms", "ray_steps", "sprites_drawn"
+                ]).map_err(CSVError)?;
+                Some(writer)
+            },
+            None => None
+        };
+
+        Ok(Self {
+            showOverlay,
+            csvPath: csvPath.unwrap_or_default().to_string(),
+            csvWriter,
+            frameIndex: 0,
+            lastFrameEnd: None,
+            updateStart: Instant::now(),
+            updateTime: Duration::ZERO,
+            averaged: FrameSample::default()
+        })
+    }
+
+    pub fn BeginUpdate(&mut self) {
+        self.updateStart = Instant::now();
+    }
+
+    pub fn EndUpdate(&mut self) {
+        self.updateTime = self.updateStart.elapsed();
+    }
+
+    // Call once per frame after presenting ; frame time is measured from the previous call, so it includes vsync waits
+    pub fn EndFrame(&mut self, renderStats: &RenderStats) {
+        let now = Instant::now();
+        let frameTime = match self.lastFrameEnd {
+            Some(lastFrameEnd) => now - lastFrameEnd,
+            None => Duration::ZERO
+        };
+        self.lastFrameEnd = Some(now);
+
+        let sample = FrameSample::New(frameTime, self.updateTime, renderStats);
+        // The first frame has no frame time yet, so start averaging from the second
+        if self.frameIndex <= 1 {
+            self.averaged = sample;
+        } else {
+            self.averaged.Blend(&sample, AVERAGE_WEIGHT);
+        }
+
+        // A failed write (e.g. a full disk) only stops the recording, not the game
+        if let Some(writer) = &mut self.csvWriter {
+            let written = writer.write_record(&[
+                self.frameIndex.to_string(),
+                format!("{:.3}", sample.frame),
+                format!("{:.3}", sample.update),
+                format!("{:.3}", sample.drawCeilingAndFloor),
+                format!("{:.3}", sample.renderIntoBuffers),
+                format!("{:.3}", sample.drawWallsFromBuffer),
+                format!("{:.3}", sample.drawSpritesFromBuffer),
+                format!("{:.3}", sample.drawWeapon),
+                renderStats.raySteps.to_string(),
+                renderStats.spritesDrawn.to_string()
+            ]);
+            if let Err(e) = written {
+                eprintln!("warning: {}: {} ; no longer recording frame timings", self.csvPath, e);
+                self.csvWriter = None;
+            }
+        }
+
+        self.frameIndex += 1;
+    }
+
+    pub fn DrawOverlay(&self, renderer: &mut dyn Renderer) {
+        if !self.showOverlay {
+            return;
+        }
+
+        let a = &self.averaged;
+        let fps = if a.frame > 0.0 { 1000.0/a.frame } else { 0.0 };
+        let lines = [
+            format!("FPS {:.0}", fps),
+            format!("FRAME {:.2} MS", a.frame),
+            format!("UPDATE {:.2} MS", a.update),
+            format!("FLOOR/CEILING {:.2} MS", a.drawCeilingAndFloor),
+            format!("RAYCAST {:.2} MS", a.renderIntoBuffers),
+            format!("WALLS {:.2} MS", a.drawWallsFromBuffer),
+            format!("SPRITES {:.2} MS", a.drawSpritesFromBuffer),
+            format!("WEAPON {:.2} MS", a.drawWeapon),
+            format!("RAY STEPS {:.0}", a.raySteps),
+            format!("SPRITES DRAWN {:.0}", a.spritesDrawn)
+        ];
+
+        let longestLine = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32;
+        let backgroundWidth = longestLine*(GLYPH_WIDTH + 1)*OVERLAY_SCALE + 2*OVERLAY_MARGIN;
+        let backgroundHeight = lines.len() as i32*OVERLAY_LINE_HEIGHT + 2*OVERLAY_MARGIN;
+        renderer.FillRect(OVERLAY_BACKGROUND_COLOR, Rect::new(0, 0, backgroundWidth as u32, backgroundHeight as u32));
+
+        for (i, line) in lines.iter().enumerate() {
+            DrawText(renderer, line, OVERLAY_MARGIN, OVERLAY_MARGIN + i as i32*OVERLAY_LINE_HEIGHT, OVERLAY_SCALE, OVERLAY_TEXT_COLOR);
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        if let Some(writer) = &mut self.csvWriter {
+            let _ = writer.flush();
+        }
+    }
+}
+
+fn DrawText(renderer: &mut dyn Renderer, text: &str, x: i32, y: i32, scale: i32, color: Color) {
+    for (i, c) in text.chars().enumerate() {
+        let glyphX = x + i as i32*(GLYPH_WIDTH + 1)*scale;
+        for (row, bits) in GetGlyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                    renderer.FillRect(color, Rect::new(glyphX + col*scale, y + row as i32*scale, scale as u32, scale as u32));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use sdl2::{pixels::Color, rect::Rect};
+use crate::{
+    framebuffer::{Framebuffer, SoftwareTextures},
+    lighting::{Lighting, Shade},
+    multimedia::{LightTexture, TextureType, WindowParams, RenderParams},
+    renderer::Renderer,
+    player::{Player, PlayerPose},
+    map::Map,
+    utils::{
+        ray::Ray,
+        dda::RayCursor, vec2d::{Dot, Point2, Vec2, iPoint2}, conventions::TEXTURE_PITCH, bmp::Bitmap
+    }, tiles::{Tile, TextureHandle, Sprite, WallSlice}
+};
+
+struct SpriteRenderData {
+    vecToSprite: Vec2,
+    spriteHitDistY: f64,
+    spriteHitDistX: f64,
+    spriteScreenX: i32,
+    spriteRenderHeight: i32,
+    spriteScreenRect: Rect,
+    spriteTextureHandle: TextureHandle
+}
+
+/*
+=========================================================
+    RenderResolution enum definition
+=========================================================
+*/
+
+// Resolution the raycast is rendered at before being scaled up to the window
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderResolution {
+    NATIVE,                 // one ray per window column
+    DIVIDED(usize),         // window size divided by n in both directions
+    FIXED(usize, usize)     // a fixed width x height stretched to the window, e.g. 320x200 like the original
+}
+
+impl RenderResolution {
+    // Parses `native`, `half`, `quarter` or `WIDTHxHEIGHT`
+    pub fn FromArg(arg: &str) -> Option<Self> {
+        match arg {
+            "native" => Some(RenderResolution::NATIVE),
+            "half" => Some(RenderResolution::DIVIDED(2)),
+            "quarter" => Some(RenderResolution::DIVIDED(4)),
+            _ => {
+                let (width, height) = arg.split_once('x')?;
+                let (width, height) = (width.parse::<usize>().ok()?, height.parse::<usize>().ok()?);
+                if width == 0 || height == 0 { None } else { Some(RenderResolution::FIXED(width, height)) }
+            }
+        }
+    }
+
+    // Next preset, for cycling at runtime
+    pub fn Next(&self) -> Self {
+        match self {
+            RenderResolution::NATIVE => RenderResolution::DIVIDED(2),
+            RenderResolution::DIVIDED(2) => RenderResolution::DIVIDED(4),
+            RenderResolution::DIVIDED(4) => RenderResolution::FIXED(320, 200),
+            _ => RenderResolution::NATIVE
+        }
+    }
+
+    pub fn FrameSize(&self, windowWidth: usize, windowHeight: usize) -> (usize, usize) {
+        match *self {
+            RenderResolution::NATIVE => (windowWidth, windowHeight),
+            RenderResolution::DIVIDED(n) => ((windowWidth/n).max(1), (windowHeight/n).max(1)),
+            RenderResolution::FIXED(width, height) => (width, height)
+        }
+    }
+}
+
+/*
+=========================================================
+    RenderStats struct definition
+=========================================================
+*/
+
+// Where the last frame's render time went, for the profiler
+#[derive(Default, Clone, Copy)]
+pub struct RenderStats {
+    pub drawCeilingAndFloor: Duration,
+    pub renderIntoBuffers: Duration,
+    pub drawWallsFromBuffer: Duration,
+    pub drawSpritesFromBuffer: Duration,
+    pub drawWeapon: Duration,
+    pub raySteps: usize,
+    pub spritesDrawn: usize
+}
+
+/*
+=========================================================
+    Raycaster struct definition
+=========================================================
+*/
+
+pub struct Raycaster {
+    pub windowParams: WindowParams,
+    pub renderParams: RenderParams,
+
+    // Size of the frame the raycast is rendered into ; same as windowParams at native resolution,
+    // otherwise frames go through the offscreen framebuffer and get upscaled to the window
+    pub renderResolution: RenderResolution,
+    pub frameParams: WindowParams,
+    offscreen: Option<Framebuffer>,
+
+    pub stats: RenderStats,
+
+    // Threads that cast columns alongside the rendering thread ; started once rather than every frame,
+    // so a frame doesn't pay for creating and joining them
+    castingPool: ThreadPool,
+
+    wallSlicesBuffer: Vec<Option<WallSlice>>,      // one entry per column, so buffer index == screen x
+    spriteTileBuffers: Vec<Vec<iPoint2>>,           // one per raycasting worker, the rendering thread included
+    spritesBuffer: Vec<Sprite>,
+    spritesRenderDataBuffer: Vec<SpriteRenderData>,
+    depthBuffer: Vec<f64>,
+    spriteTileHitMap: Vec<Vec<bool>>,
+    weaponRenderRect: Rect,
+    horizon: i32,
+
+    // Floor & ceiling casting
+    softwareTextures: SoftwareTextures,
+    flatsBuffer: Bitmap,
+}
+
+// Sprites nearer than this (perpendicular to the view plane) are not drawn
+const SPRITE_NEAR_PLANE: f64 = 0.1;
+
+const CEILING_COLOR: [u8; 4] = [50, 50, 50, 255];
+const FLOOR_COLOR: [u8; 4] = [96, 96, 96, 255];
+
+impl Raycaster {
+    pub fn New(windowWidth: usize, windowHeight: usize, fov: f64, map: &Map) -> Self {
+        Self::WithTextures(windowWidth, windowHeight, fov, map, SoftwareTextures::New())
+    }
+
+    pub fn WithTextures(windowWidth: usize, windowHeight: usize, fov: f64, map: &Map, softwareTextures: SoftwareTextures) -> Self {
+        let spriteTileHitMap: Vec<Vec<bool>> = vec![vec![false; map.height as usize]; map.width as usize];
+        let numWorkers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let castingPool = ThreadPoolBuilder::new()
+            .num_threads((numWorkers - 1).max(1))
+            .thread_name(|i| format!("raycaster-{}", i))
+            .build()
+            .expect("couldn't start the raycasting threads");
+
+        let mut raycaster = Self {
+            windowParams: WindowParams{width: 0, height: 0},
+            renderParams: RenderParams::New(fov, 0, 1.0),
+
+            renderResolution: RenderResolution::NATIVE,
+            frameParams: WindowParams{width: 0, height: 0},
+            offscreen: None,
+
+            stats: RenderStats::default(),
+
+            castingPool,
+
+            wallSlicesBuffer: Vec::new(),
+            spriteTileBuffers: vec![Vec::new(); numWorkers],
+            spritesBuffer: Vec::new(),
+            spritesRenderDataBuffer: Vec::new(),
+            depthBuffer: Vec::new(),
+
+            spriteTileHitMap,
+
+            weaponRenderRect: Rect::new(0, 0, 1, 1),
+            horizon: 0,
+
+            softwareTextures,
+            flatsBuffer: Bitmap {
+                width: 0,
+                height: 0,
+                pixels: Vec::new()
+            },
+        };
+        raycaster.Resize(windowWidth, windowHeight);
+
+        raycaster
+    }
+
+    // Rebuilds everything that depends on the window size ; called on startup and whenever the window is resized
+    pub fn Resize(&mut self, windowWidth: usize, windowHeight: usize) {
+        self.windowParams = WindowParams{width: windowWidth, height: windowHeight};
+        let (frameWidth, frameHeight) = self.renderResolution.FrameSize(windowWidth, windowHeight);
+        self.frameParams = WindowParams{width: frameWidth, height: frameHeight};
+
+        let pixelAspect = (windowHeight as f64/frameHeight as f64) / (windowWidth as f64/frameWidth as f64);
+        self.renderParams = RenderParams::New(self.renderParams.fov, frameWidth, pixelAspect);
+
+        if self.frameParams.width == windowWidth && self.frameParams.height == windowHeight {
+            self.offscreen = None;
+        } else {
+            match &mut self.offscreen {
+                Some(framebuffer) => framebuffer.Resize(frameWidth, frameHeight),
+                None => self.offscreen = Some(Framebuffer::WithTextures(frameWidth, frameHeight, self.softwareTextures.clone()))
+            }
+        }
+
+        // Perpendicular distance to the nearest wall slice in each column
+        self.depthBuffer = vec![f64::INFINITY; frameWidth];
+
+        // Weapon is half the frame wide at 16:9, but never taller than the frame allows
+        let weaponRenderHeight = ((frameWidth/2) as f64 / pixelAspect).min((frameHeight*8/9) as f64);
+        let weaponRenderWidth = weaponRenderHeight * pixelAspect;
+        self.weaponRenderRect = Rect::new(
+            (frameWidth as f64/2.0 - weaponRenderWidth/2.0) as i32,
+            frameHeight as i32 - weaponRenderHeight as i32,
+            weaponRenderWidth.max(1.0) as u32,
+            weaponRenderHeight.max(1.0) as u32
+        );
+
+        self.horizon = (frameHeight/2) as i32;
+        self.flatsBuffer = Bitmap {
+            width: frameWidth,
+            height: frameHeight,
+            pixels: vec![[0, 0, 0, 255]; frameWidth*frameHeight]
+        };
+    }
+
+    pub fn SetRenderResolution(&mut self, renderResolution: RenderResolution) {
+        self.renderResolution = renderResolution;
+        self.Resize(self.windowParams.width, self.windowParams.height);
+    }
+
+    // Draws a frame but leaves presenting it to the caller, so overlays can go on top. The view is drawn from pose,
+    // which may be part way between ticks, and the weapon from player ; neither is changed
+    pub fn RenderFrame(&mut self, renderer: &mut dyn Renderer, pose: &PlayerPose, player: &Player, map: &Map) {
+        match self.offscreen.take() {
+            Some(mut offscreen) => {
+                self.DrawFrame(&mut offscreen, pose, player, map);
+                renderer.Clear();
+                renderer.DrawBitmap(&offscreen.frame, Rect::new(0, 0, self.windowParams.width as u32, self.windowParams.height as u32));
+                self.offscreen = Some(offscreen);
+            },
+            None => self.DrawFrame(renderer, pose, player, map)
+        }
+    }
+
+    fn DrawFrame(&mut self, renderer: &mut dyn Renderer, pose: &PlayerPose, player: &Player, map: &Map) {
+        // Looking up/down shears the view vertically rather than tilting the camera
+        self.horizon = (self.frameParams.height/2) as i32 + (pose.pitch*self.frameParams.height as f64) as i32;
+
+        renderer.Clear();
+
+        let mut stageStart = Instant::now();
+        let mut EndStage = || {
+            let now = Instant::now();
+            let stageTime = now - stageStart;
+            stageStart = now;
+            stageTime
+        };
+
+        self.DrawCeilingAndFloor(renderer, pose, map);
+        self.stats.drawCeilingAndFloor = EndStage();
+        self.stats.raySteps = self.RenderIntoBuffers(pose, map);
+        self.stats.renderIntoBuffers = EndStage();
+        self.DrawWallsFromBuffer(renderer, &map.lighting);
+        self.stats.drawWallsFromBuffer = EndStage();
+        self.stats.spritesDrawn = self.DrawSpritesFromBuffer(renderer, pose, &map.lighting);
+        self.stats.drawSpritesFromBuffer = EndStage();
+        self.DrawWeapon(renderer, player);
+        self.stats.drawWeapon = EndStage();
+    }
+
+    fn DrawCeilingAndFloor(&mut self, renderer: &mut dyn Renderer, pose: &PlayerPose, map: &Map) {
+        // Evenly lit maps without any floor/ceiling textures keep the cheap flat fills
+        if !map.HasTexturedFlats() && map.lighting.IsUniform() {
+            let horizon = self.horizon.clamp(0, self.frameParams.height as i32);
+            renderer.FillRect(Color::RGBA(CEILING_COLOR[0], CEILING_COLOR[1], CEILING_COLOR[2], 255), Rect::new(0, 0, self.frameParams.width as u32, horizon as u32));
+            renderer.FillRect(Color::RGBA(FLOOR_COLOR[0], FLOOR_COLOR[1], FLOOR_COLOR[2], 255), Rect::new(0, horizon, self.frameParams.width as u32, self.frameParams.height as u32 - horizon as u32));
+            return;
+        }
+
+        self.CastFlats(pose, map);
+        renderer.DrawBitmap(&self.flatsBuffer, Rect::new(0, 0, self.frameParams.width as u32, self.frameParams.height as u32));
+    }
+
+    // Per-row floor & ceiling casting. A floor point at perpendicular distance d lands
+    // renderHeightProprConst/(2d) pixels below the horizon (the bottom edge of a wall at d),
+    // so each screen row maps to one distance ; ceiling rows are the same, measured above the horizon.
+    fn CastFlats(&mut self, pose: &PlayerPose, map: &Map) {
+        let width = self.frameParams.width;
+        let height = self.frameParams.height;
+        let horizon = self.horizon;
+        let pitch = TEXTURE_PITCH as f64;
+
+        for y in 0..height {
+            let isFloor = y as i32 >= horizon;
+            let rowOffset = if isFloor { (y as i32 - horizon) as f64 + 0.5 } else { (horizon - y as i32) as f64 - 0.5 };
+            let rowDist = self.renderParams.renderHeightProprConst / (2.0*rowOffset);
+            let shade = map.lighting.GetShade(rowDist);
+            let flatColor = if isFloor { FLOOR_COLOR } else { CEILING_COLOR };
+
+            // World point under the leftmost column, and the step between columns
+            let leftmostDir = pose.viewDir + pose.East()*(-(width as f64)/2.0/self.renderParams.projPlaneDist);
+            let mut worldPoint = pose.location + leftmostDir*rowDist;
+            let step = pose.East()*(rowDist/self.renderParams.projPlaneDist);
+
+            // Consecutive columns mostly stay on the same tile, so only look textures up when the tile changes
+            let mut cachedTile = iPoint2::New(-1, -1);
+            let mut flatTexture: Option<&Bitmap> = None;
+
+            for x in 0..width {
+                let tile: iPoint2 = worldPoint.into();
+                if tile != cachedTile {
+                    cachedTile = tile;
+                    let insideMap = worldPoint.x() >= 0.0 && worldPoint.y() >= 0.0 && tile.x() < map.width && tile.y() < map.height;
+                    let textureHandle = if !insideMap { None } else if isFloor { map.GetFloorTexture(tile) } else { map.GetCeilingTexture(tile) };
+                    flatTexture = textureHandle.map(|h| self.softwareTextures.GetTexture(h));
+                }
+
+                let texelX = ((worldPoint.x() - worldPoint.x().floor())*pitch) as usize;
+                let texelY = ((worldPoint.y() - worldPoint.y().floor())*pitch) as usize;
+
+                self.flatsBuffer.pixels[y*width + x] = shade.Apply(match flatTexture {
+                    Some(texture) => texture.GetPixel(texelX, texelY),
+                    None => flatColor
+                });
+
+                worldPoint = worldPoint + step;
+            }
+        }
+    }
+
+    // Casts all columns in parallel on the casting pool, then merges the sprite tiles each worker passed through into spritesBuffer.
+    // Returns the total number of DDA steps taken.
+    fn RenderIntoBuffers(&mut self, pose: &PlayerPose, map: &Map) -> usize {
+        self.wallSlicesBuffer.clear();
+        self.wallSlicesBuffer.resize(self.frameParams.width, None);
+        self.spritesBuffer.clear();
+        self.ResetSpriteTileHitMap();
+        for spriteTiles in self.spriteTileBuffers.iter_mut() {
+            spriteTiles.clear();
+        }
+
+        let location = pose.location;
+        let viewDir = pose.viewDir;
+        let columnsPerWorker = self.frameParams.width.div_ceil(self.spriteTileBuffers.len()).max(1);
+        let rayAngleChunks = self.renderParams.castingRayAngles.chunks(columnsPerWorker);
+        let wallSliceChunks = self.wallSlicesBuffer.chunks_mut(columnsPerWorker);
+        let mut jobs = rayAngleChunks.zip(wallSliceChunks).zip(self.spriteTileBuffers.iter_mut());
+
+        // Last chunk goes on this thread rather than idling while the others work
+        let ownJob = jobs.next_back();
+        let raySteps = AtomicUsize::new(0);
+        self.castingPool.in_place_scope(|scope| {
+            for ((rayAngles, wallSlices), spriteTiles) in jobs {
+                let raySteps = &raySteps;
+                scope.spawn(move |_| {
+                    raySteps.fetch_add(CastColumns(map, location, viewDir, rayAngles, wallSlices, spriteTiles), Ordering::Relaxed);
+                });
+            }
+            if let Some(((rayAngles, wallSlices), spriteTiles)) = ownJob {
+                raySteps.fetch_add(CastColumns(map, location, viewDir, rayAngles, wallSlices, spriteTiles), Ordering::Relaxed);
+            }
+        });
+        let raySteps = raySteps.into_inner();
+
+        // Merge step ; tiles seen by several columns (or workers) contribute their sprites once
+        for i in 0..self.spriteTileBuffers.len() {
+            for j in 0..self.spriteTileBuffers[i].len() {
+                let tileCoord = self.spriteTileBuffers[i][j];
+                self.GrabSprites(tileCoord, map);
+            }
+        }
+
+        raySteps
+    }
+
+    fn DrawWallsFromBuffer(&mut self, renderer: &mut dyn Renderer, lighting: &Lighting) {
+        self.ResetDepthBuffer();
+        for x in 0..self.wallSlicesBuffer.len() {
+            let wallSlice = match &self.wallSlicesBuffer[x] {
+                Some(wallSlice) => wallSlice,
+                None => continue
+            };
+
+            let distToHitPoint = wallSlice.dist;
+            let perpDist = distToHitPoint * self.renderParams.castingRayAngles[x as usize].1;
+            let renderHeight = (self.renderParams.renderHeightProprConst / perpDist) as i32;
+            let screenY = self.horizon - (renderHeight / 2);
+            let screenRect = Rect::new(x as i32, screenY, 1, renderHeight as u32);
+            self.depthBuffer[x as usize] = perpDist;
+
+            // Render onto screen
+            renderer.CopyTexture(wallSlice.textureHandle, wallSlice.textureRect, screenRect, lighting.GetShade(perpDist));
+        }
+    }
+
+    // Returns how many sprites had at least one column drawn
+    fn DrawSpritesFromBuffer(&mut self, renderer: &mut dyn Renderer, pose: &PlayerPose, lighting: &Lighting) -> usize {
+        self.spritesRenderDataBuffer.clear();
+        for sprite in &self.spritesBuffer {
+            let vecToSprite = sprite.location - pose.location;
+            let spriteHitDistY = Dot(vecToSprite, pose.viewDir);
+
+            // Sprites are billboards parallel to the camera plane, so they are either wholly in front of it or not at all.
+            // Anything closer than the near plane is skipped rather than projected through a near-zero or negative depth.
+            if spriteHitDistY < SPRITE_NEAR_PLANE {
+                continue;
+            }
+
+            let spriteHitDistX = Dot(vecToSprite, pose.East());
+            let spriteScreenX = ((self.frameParams.width/2) as f64 + ((self.renderParams.projPlaneDist/spriteHitDistY)*spriteHitDistX)) as i32;
+            let spriteRenderHeight = (self.renderParams.renderHeightProprConst / spriteHitDistY) as i32;
+            let spriteRenderWidth = ((spriteRenderHeight as f64 * self.renderParams.pixelAspect) as i32).max(1);
+            let spriteScreenRect = Rect::new(spriteScreenX - (spriteRenderWidth/2), self.horizon - (spriteRenderHeight/2), spriteRenderWidth as u32, spriteRenderHeight as u32);
+            let spriteTextureHandle = sprite.textureHandle;
+
+            self.spritesRenderDataBuffer.push(SpriteRenderData {
+                vecToSprite,
+                spriteHitDistY,
+                spriteHitDistX,
+                spriteScreenX,
+                spriteRenderHeight,
+                spriteScreenRect,
+                spriteTextureHandle
+            });
+        }
+
+        // Painter's order: farthest first
+        self.spritesRenderDataBuffer.sort_by(|a, b| b.spriteHitDistY.total_cmp(&a.spriteHitDistY));
+
+        let mut spritesDrawn = 0;
+        for s in &self.spritesRenderDataBuffer {
+            let shade = lighting.GetShade(s.spriteHitDistY);
+            let firstX = s.spriteScreenRect.x.max(0);
+            let lastX = (s.spriteScreenRect.x + s.spriteScreenRect.w).min(self.frameParams.width as i32);
+            let mut anyColumnDrawn = false;
+            for x in firstX..lastX {
+                // Column is hidden if a wall slice in it is nearer than the sprite
+                if s.spriteHitDistY < self.depthBuffer[x as usize] {
+                    anyColumnDrawn = true;
+                    let spriteTextureWidthPercent = (x - s.spriteScreenRect.x) as f64 / (s.spriteScreenRect.w) as f64;
+                    let spriteTextureX = (spriteTextureWidthPercent * TEXTURE_PITCH as f64) as i32;
+                    let spriteTextureRect = Rect::new(spriteTextureX, 0, 1, TEXTURE_PITCH);
+                    let screenRect = Rect::new(x, s.spriteScreenRect.y, 1, s.spriteScreenRect.h as u32);
+
+                    renderer.CopyTexture(s.spriteTextureHandle, spriteTextureRect, screenRect, shade);
+                }
+            }
+            if anyColumnDrawn {
+                spritesDrawn += 1;
+            }
+        }
+
+        spritesDrawn
+    }
+
+    fn DrawWeapon(&mut self, renderer: &mut dyn Renderer, player: &Player) {
+        let screenRect = self.weaponRenderRect;
+        let textureHandle = player.AM_weapon.GetCurrTexture();
+        let textureRect = Rect::new(0, 0, TEXTURE_PITCH, TEXTURE_PITCH);
+        renderer.CopyTexture(textureHandle, textureRect, screenRect, Shade::FULL_BRIGHT);
+    }
+
+    fn ResetSpriteTileHitMap(&mut self) {
+        for column in self.spriteTileHitMap.iter_mut() {
+            column.fill(false);
+        }
+    }
+
+    fn ResetDepthBuffer(&mut self) {
+        self.depthBuffer.fill(f64::INFINITY);
+    }
+
+    fn GrabSprites(&mut self, tileCoord: iPoint2, map: &Map) {
+        let currTileX = tileCoord.x() as usize;
+        let currTileY = tileCoord.y() as usize;
+        if self.spriteTileHitMap[currTileX][currTileY] == false {
+            match map.GetTile(tileCoord) {
+                Tile::OBJECT(objectTile) => {
+                    self.spritesBuffer.push(objectTile.objectSprite);
+                    if objectTile.IsEnemyHolder() {
+                        let spritesArr = objectTile.GetSprites();
+                        if spritesArr.is_some() {
+                            for s in spritesArr.unwrap() {
+                                self.spritesBuffer.push(*s);
+                            }
+                        }
+                    }
+                },
+                Tile::EMPTY(emptyTile) => {
+                    let spritesArr = emptyTile.GetSprites();
+                    if spritesArr.is_some() {
+                        for s in spritesArr.unwrap() {
+                            self.spritesBuffer.push(*s);
+                        }
+                    }
+                }
+                Tile::DOOR(door) => {
+                    if let Some(spritesArr) = door.GetSprites() {
+                        self.spritesBuffer.extend(spritesArr);
+                    }
+                }
+                _ => panic!()
+            }
+            self.spriteTileHitMap[currTileX][currTileY] = true;
+        }
+    }
+}
+
+// Read-only DDA walk for a run of consecutive columns: fills in each column's nearest wall slice (None if the ray
+// leaves the map) and records every object/empty/door tile passed on the way, for sprite collection afterwards.
+// Returns the number of DDA steps taken.
+fn CastColumns(map: &Map, location: Point2, viewDir: Vec2, rayAngles: &[(f64, f64)], wallSlices: &mut [Option<WallSlice>], spriteTiles: &mut Vec<iPoint2>) -> usize {
+    let mut raySteps = 0;
+    for (x, rayAngle) in rayAngles.iter().enumerate() {
+        let currRay = Ray::New(location, viewDir.Rotate(rayAngle.0));
+        let mut rayCursor = RayCursor::New(currRay, location);
+        let mut prevTileCoord = rayCursor.hitTile;
+        let mut columnWallSlice: Option<WallSlice> = None;
+        while map.WithinMap(rayCursor.hitTile) {
+            let prevTileWasDoor = if let Tile::DOOR(_) = map.GetTile(prevTileCoord) { true } else { false };
+            rayCursor.GoToNextHit();
+            raySteps += 1;
+            let currTileCoord = rayCursor.hitTile;
+            prevTileCoord = currTileCoord;
+
+            match map.GetTile(currTileCoord) {
+                Tile::WALL(wall) => {
+                    let mut wallSlice = wall.GetWallSlice(&mut rayCursor);
+                    if prevTileWasDoor {
+                        let gateSidewall_lit = TextureHandle::New(TextureType::WALL, 101);
+                        let gateSideWall_unlit = TextureHandle::New(TextureType::WALL, 102);
+                        wallSlice.textureHandle = LightTexture(&mut rayCursor, gateSidewall_lit, gateSideWall_unlit);
+                    }
+                    columnWallSlice = Some(wallSlice);
+                    break;
+                },
+                Tile::DOOR(door) => {
+                    // Enemies in the doorway ; depth testing sorts out which side of the gate they're on
+                    if spriteTiles.last() != Some(&currTileCoord) {
+                        spriteTiles.push(currTileCoord);
+                    }
+                    let doorWallSlice = door.GetWallSlice(&mut rayCursor);
+                    if doorWallSlice.is_some() {
+                        columnWallSlice = doorWallSlice;
+                        break;
+                    } else {
+                        continue;
+                    }
+                },
+                Tile::OBJECT(_) | Tile::EMPTY(_) => {
+                    // Neighbouring rays mostly cross the same tiles ; skip the obvious repeats
+                    if spriteTiles.last() != Some(&currTileCoord) {
+                        spriteTiles.push(currTileCoord);
+                    }
+                },
+                Tile::NONE => panic!(),
+            };
+        }
+
+        wallSlices[x] = columnWallSlice;
+    }
+
+    raySteps
+}
@@ -0,0 +1,22 @@
+use sdl2::{pixels::Color, rect::Rect};
+use crate::lighting::Shade;
+use crate::tiles::TextureHandle;
+use crate::utils::bmp::Bitmap;
+
+/*
+=========================================================
+    Renderer trait
+=========================================================
+*/
+
+// Everything the raycaster needs to put a frame on screen. Implemented by the SDL
+// window canvas (Multimedia) and by the headless software Framebuffer.
+pub trait Renderer {
+    fn Clear(&mut self);
+    fn FillRect(&mut self, color: Color, rect: Rect);
+    fn CopyTexture(&mut self, textureHandle: TextureHandle, srcRect: Rect, dstRect: Rect, shade: Shade);
+
+    // Opaque, nearest-neighbour scaled blit of a CPU-side pixel buffer (e.g. per-pixel floor casting)
+    fn DrawBitmap(&mut self, bitmap: &Bitmap, dstRect: Rect);
+    fn Present(&mut self);
+}
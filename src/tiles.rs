@@ -29,6 +29,7 @@ impl TextureHandle {
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct WallSlice {
     pub textureHandle: TextureHandle,
     pub textureRect: Rect,
@@ -103,6 +104,7 @@ pub struct Door {
     pub position: f64,
     pub status: DoorStatus,
     pub timerVal: f64,
+    pub enemySprites: Vec<Sprite>
 }
 
 impl Door {
@@ -111,6 +113,23 @@ impl Door {
             position: DoorPosition::CLOSED as i32 as f64,
             status: DoorStatus::CLOSED,
             timerVal: DoorTimerVal::FULL_TIME_LEFT as i32 as f64,
+            enemySprites: Vec::new()
+        }
+    }
+
+    // Does nothing to a door that's already open or opening
+    pub fn Open(&mut self) {
+        if self.status == DoorStatus::CLOSED || self.status == DoorStatus::CLOSING {
+            self.status = DoorStatus::OPENING;
+        }
+    }
+
+    // Enemies walking through the doorway
+    pub fn GetSprites(&self) -> Option<&Vec<Sprite>> {
+        if self.enemySprites.is_empty() {
+            None
+        } else {
+            Some(&self.enemySprites)
         }
     }
 
@@ -157,11 +176,12 @@ impl Door {
         self.position > 0.2
     }
 
-    pub fn Update(&mut self, moveIncr: f64, timerIncr: f64, playerInsideDoor: bool) {    
+    // An occupied doorway, by the player or an enemy, holds the door open ; one that starts closing on someone opens again
+    pub fn Update(&mut self, moveIncr: f64, timerIncr: f64, doorOccupied: bool) {    
         match self.status {
             DoorStatus::CLOSED => {},
             DoorStatus::OPEN => {
-                if !playerInsideDoor {
+                if !doorOccupied {
                     self.timerVal -= timerIncr;
                     if self.timerVal < 0.0 {
                         self.status = DoorStatus::CLOSING;
@@ -179,10 +199,14 @@ impl Door {
                 }
             },
             DoorStatus::CLOSING => {
-                self.position += moveIncr;
-                if self.position > 1.0 {
-                    self.position = 1.0;
-                    self.status = DoorStatus::CLOSED;
+                if doorOccupied {
+                    self.status = DoorStatus::OPENING;
+                } else {
+                    self.position += moveIncr;
+                    if self.position > 1.0 {
+                        self.position = 1.0;
+                        self.status = DoorStatus::CLOSED;
+                    }
                 }
             }
         }
@@ -0,0 +1,150 @@
+use std::error::Error;
+use std::fs;
+use crate::load_error::LoadError;
+
+/*
+=========================================================
+    Bitmap struct definition
+=========================================================
+*/
+
+// Decoded image, stored top-down as RGBA texels
+#[derive(Clone)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 4]>
+}
+
+impl Bitmap {
+    pub fn GetPixel(&self, x: usize, y: usize) -> [u8; 4] {
+        self.pixels[y*self.width + x]
+    }
+}
+
+/*
+=========================================================
+    Loading & saving
+=========================================================
+*/
+
+fn ReadU16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset+1]])
+}
+
+fn ReadU32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset+1], bytes[offset+2], bytes[offset+3]])
+}
+
+fn MaskedChannel(pixel: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 255;
+    }
+    ((pixel & mask) >> mask.trailing_zeros()) as u8
+}
+
+// Reads uncompressed 24-bit and 32-bit (BI_RGB or BI_BITFIELDS) BMP files, which covers every sheet in assets/
+pub fn LoadBMP(path: &str) -> Result<Bitmap, LoadError> {
+    let bytes = fs::read(path).map_err(|error| LoadError::IO { path: path.to_string(), error })?;
+    let Invalid = |message: String| LoadError::IMAGE { path: path.to_string(), message };
+
+    if bytes.len() < 54 || &bytes[0..2] != b"BM" {
+        return Err(Invalid("not a BMP file".to_string()));
+    }
+
+    let pixelDataOffset = ReadU32(&bytes, 10) as usize;
+    let dibHeaderSize = ReadU32(&bytes, 14) as usize;
+    let width = ReadU32(&bytes, 18) as i32;
+    let rawHeight = ReadU32(&bytes, 22) as i32;
+    let bitsPerPixel = ReadU16(&bytes, 28);
+    let compression = ReadU32(&bytes, 30);
+
+    if width <= 0 || rawHeight == 0 {
+        return Err(Invalid(format!("invalid dimensions {}x{}", width, rawHeight)));
+    }
+
+    // Default masks for 32-bit BI_RGB are BGRA byte order
+    let (rMask, gMask, bMask, aMask) = match (bitsPerPixel, compression) {
+        (24, 0) => (0, 0, 0, 0),
+        (32, 0) => (0x00FF0000, 0x0000FF00, 0x000000FF, 0),
+        (32, 3) => {
+            if dibHeaderSize >= 56 {
+                (ReadU32(&bytes, 54), ReadU32(&bytes, 58), ReadU32(&bytes, 62), ReadU32(&bytes, 66))
+            } else {
+                (ReadU32(&bytes, 54), ReadU32(&bytes, 58), ReadU32(&bytes, 62), 0)
+            }
+        },
+        _ => return Err(Invalid(format!("unsupported BMP format ({} bpp, compression {})", bitsPerPixel, compression)))
+    };
+
+    let width = width as usize;
+    let height = rawHeight.unsigned_abs() as usize;
+    let bottomUp = rawHeight > 0;
+    let bytesPerPixel = (bitsPerPixel / 8) as usize;
+    let rowStride = (width*bytesPerPixel + 3) & !3;
+
+    if bytes.len() < pixelDataOffset + rowStride*height {
+        return Err(Invalid("pixel data is truncated".to_string()));
+    }
+
+    let mut pixels: Vec<[u8; 4]> = vec![[0, 0, 0, 0]; width*height];
+    for row in 0..height {
+        let y = if bottomUp { height - 1 - row } else { row };
+        let rowStart = pixelDataOffset + row*rowStride;
+        for x in 0..width {
+            let p = rowStart + x*bytesPerPixel;
+            pixels[y*width + x] = if bytesPerPixel == 3 {
+                [bytes[p+2], bytes[p+1], bytes[p], 255]
+            } else {
+                let pixel = ReadU32(&bytes, p);
+                [MaskedChannel(pixel, rMask), MaskedChannel(pixel, gMask), MaskedChannel(pixel, bMask), MaskedChannel(pixel, aMask)]
+            };
+        }
+    }
+
+    Ok(Bitmap {
+        width,
+        height,
+        pixels
+    })
+}
+
+// Writes a bottom-up 24-bit BMP ; alpha is dropped
+pub fn SaveBMP(path: &str, bitmap: &Bitmap) -> Result<(), Box<dyn Error>> {
+    let rowStride = (bitmap.width*3 + 3) & !3;
+    let pixelDataSize = rowStride*bitmap.height;
+    let fileSize = 54 + pixelDataSize;
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(fileSize);
+
+    // File header
+    bytes.extend_from_slice(b"BM");
+    bytes.extend_from_slice(&(fileSize as u32).to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&54u32.to_le_bytes());
+
+    // BITMAPINFOHEADER
+    bytes.extend_from_slice(&40u32.to_le_bytes());
+    bytes.extend_from_slice(&(bitmap.width as i32).to_le_bytes());
+    bytes.extend_from_slice(&(bitmap.height as i32).to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&24u16.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&(pixelDataSize as u32).to_le_bytes());
+    bytes.extend_from_slice(&2835i32.to_le_bytes());
+    bytes.extend_from_slice(&2835i32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+
+    for y in (0..bitmap.height).rev() {
+        let rowStart = bytes.len();
+        for x in 0..bitmap.width {
+            let [r, g, b, _] = bitmap.GetPixel(x, y);
+            bytes.extend_from_slice(&[b, g, r]);
+        }
+        bytes.resize(rowStart + rowStride, 0);
+    }
+
+    fs::write(path, bytes)?;
+    Ok(())
+}
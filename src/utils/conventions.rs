@@ -2,6 +2,9 @@ pub const PI: f64                 = std::f64::consts::PI;
 pub const TEXTURE_PITCH: u32      = 64;
 pub const TRANSPARENCY_COLOR: u32 = 0xFF980088;
 
+// Simulation ticks per second ; every per-tick speed and animation increment is tuned for this rate
+pub const TICK_RATE: u32          = 60;
+
 #[derive(Default, Copy, Clone)]
 pub enum xDir_t {
     EAST = 1,
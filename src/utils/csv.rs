@@ -1,40 +1,43 @@
-use std::error::Error;
-use std::fs::File;
+use std::collections::HashMap;
+use std::fs;
 use csv::ReaderBuilder;
 use ndarray::{Array2, s};
+use crate::load_error::{CellOrigin, LoadError};
 
-pub fn ParseCSV(path: &str) -> Result<Array2<(String, Option<i32>)>, Box<dyn Error>> {
-    // Open the file
-    let file = File::open(path)?;
+// Reads the whole file with every line ending (\r\n, \n or a lone \r, as some spreadsheets save) turned into \n,
+// so line numbers in error messages match what an editor shows
+pub fn ReadLines(path: &str) -> Result<String, LoadError> {
+    let contents = fs::read_to_string(path).map_err(|error| LoadError::IO { path: path.to_string(), error })?;
+    Ok(contents.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+// Every field of the file, with where it came from ; what the fields mean is up to the caller
+pub fn ParseCSV(path: &str) -> Result<Array2<CellOrigin>, LoadError> {
+    let contents = ReadLines(path)?;
 
     // Build CSV reader with ',' as delimiter and flexible number of fields
     let mut rdr = ReaderBuilder::new()
         .delimiter(b',')
         .flexible(true)
         .has_headers(false)  // Do not treat first row as headers
-        .from_reader(file);
+        .comment(Some(b'#'))  // Header lines are read separately by ParseCSVHeader
+        .from_reader(contents.as_bytes());
 
     let mut array_data = Vec::new();
 
     // Read each record
     for result in rdr.records() {
-        let record = result?;
+        let record = result.map_err(|error| LoadError::CSV { path: path.to_string(), error })?;
 
         // Skip empty rows
         if record.iter().next().is_none() {
             continue;
         }
 
+        let line = record.position().map_or(0, |position| position.line());
         let mut row = Vec::new();
-        for field in record.iter() {
-            // Split the field into parts by '-'
-            let parts: Vec<&str> = field.split('-').collect();
-            // First part is a string and the second part is an integer (if present)
-            let value = (
-                parts[0].to_string(), 
-                parts.get(1).and_then(|s| s.parse::<i32>().ok())
-            );
-            row.push(value);
+        for (i, field) in record.iter().enumerate() {
+            row.push(CellOrigin { text: field.to_string(), line, column: i + 1 });
         }
 
         array_data.push(row);
@@ -43,16 +46,16 @@ pub fn ParseCSV(path: &str) -> Result<Array2<(String, Option<i32>)>, Box<dyn Err
     // Find maximum row length to handle jagged arrays
     let max_len = array_data.iter().map(|row| row.len()).max().unwrap_or(0);
 
-    // Normalize rows to have equal length
+    // Normalize rows to have equal length ; the padding is empty fields
     for row in &mut array_data {
-        let diff = max_len - row.len();
-        if diff > 0 {
-            row.extend(vec![("".to_string(), None); diff]);
+        let line = row.first().map_or(0, |field| field.line);
+        for column in row.len()+1..=max_len {
+            row.push(CellOrigin { text: String::new(), line, column });
         }
     }
 
     // Convert the data to a 2D array
-    let array: Array2<(String, Option<i32>)> = ndarray::Array::from_shape_vec((array_data.len(), max_len), array_data.concat())
+    let array: Array2<CellOrigin> = ndarray::Array::from_shape_vec((array_data.len(), max_len), array_data.concat())
         .expect("Error converting to 2D array");
 
     // Flip the array both horizontally and vertically
@@ -61,3 +64,18 @@ pub fn ParseCSV(path: &str) -> Result<Array2<(String, Option<i32>)>, Box<dyn Err
     Ok(flipped_array)
 }
 
+// Map settings live in `# key = value` lines at the top of the CSV ; any other `#` line is a plain comment
+pub fn ParseCSVHeader(path: &str) -> Result<HashMap<String, String>, LoadError> {
+    let contents = ReadLines(path)?;
+    let mut header: HashMap<String, String> = HashMap::new();
+
+    for line in contents.lines() {
+        if let Some(comment) = line.trim_start().strip_prefix('#') {
+            if let Some((key, value)) = comment.split_once('=') {
+                header.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+
+    Ok(header)
+}
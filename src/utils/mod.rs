@@ -1,6 +1,10 @@
+pub mod bmp;
 pub mod conventions;
 pub mod csv;
 pub mod dda;
+pub mod font;
 pub mod misc_math;
 pub mod ray;
+#[cfg(test)]
+pub mod scratch;
 pub mod vec2d;
\ No newline at end of file
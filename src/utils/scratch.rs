@@ -0,0 +1,22 @@
+use std::fs;
+
+/*
+=========================================================
+    Scratch files for tests
+
+    Fixtures the tests write out to load back in, kept under each test
+    module's own directory in target/ so they never land in the repo.
+=========================================================
+*/
+
+// Where fileName goes in dir, making dir if needed ; for files written by the code under test
+pub fn ScratchPath(dir: &str, fileName: &str) -> String {
+    fs::create_dir_all(dir).unwrap();
+    format!("{}/{}", dir, fileName)
+}
+
+pub fn WriteScratchFile(dir: &str, fileName: &str, contents: impl AsRef<[u8]>) -> String {
+    let path = ScratchPath(dir, fileName);
+    fs::write(&path, contents).unwrap();
+    path
+}
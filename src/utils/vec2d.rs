@@ -172,7 +172,6 @@ pub fn Dot(v1: Vec2D<f64>, v2: Vec2D<f64>) -> f64 {
     v1.x()*v2.x() + v1.y()*v2.y()
 }
 
-pub fn RandomUnitVec() -> Vec2 {
-    let mut rng = rand::thread_rng();
+pub fn RandomUnitVec(rng: &mut impl Rng) -> Vec2 {
     Vec2::New({if rng.gen::<bool>() { 1.0 } else { -1.0 }}*rng.gen::<f64>(), {if rng.gen::<bool>() { 1.0 } else { -1.0 }}*rng.gen::<f64>()).UnitVector()
 }
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fs;
+use crate::load_error::LoadError;
+use crate::multimedia::{TextureType, TEXTURE_SHEET_SPECS};
+use crate::utils::bmp::Bitmap;
+use crate::utils::conventions::TEXTURE_PITCH;
+
+/*
+=========================================================
+    VSWAP file format
+
+    The original game keeps its wall textures, sprites and digitized sounds
+    in one file of chunks: a header of three u16s (chunk count, first sprite
+    chunk, first sound chunk), then a u32 offset and a u16 length per chunk.
+
+    Wall chunks are 64x64 palette indices stored column by column. Sprite
+    chunks are compressed into posts: the first and last used column, an
+    offset per column to its post list, and per post three u16s (end row*2,
+    offset of the post's pixels minus its start row, start row*2), ended by
+    a 0. Rows outside every post are transparent. Colours come from a 256
+    entry palette, which isn't in VSWAP but in the game's executable.
+=========================================================
+*/
+
+pub type Palette = [[u8; 3]; 256];
+
+/*
+=========================================================
+    Sprite layout
+
+    Which VSWAP sprite fills each slot of our texture sheets, as laid out in
+    the registered version (VSWAP.WL6). Walls and objects are in the same
+    order as our sheets ; enemies are too, apart from the slots below.
+=========================================================
+*/
+
+const FIRST_OBJECT_SPRITE: usize = 0;       // demo and death cam sprites, then the 48 static objects
+const FIRST_GUARD_SPRITE: usize = 50;
+const FIRST_SS_SPRITE: usize = 138;
+const FIRST_OFFICER_SPRITE: usize = 238;
+const NUM_WEAPON_SPRITES: usize = 20;       // knife, pistol, machine gun and chain gun ; the last sprites in the file
+
+// Sprite (counted from the enemy's first) in each of the 51 enemy sheet slots. All enemies have 8 standing and
+// 4x8 walking frames, a pain frame, three death frames, a second pain frame, any further death frames, a dead
+// body and shooting frames ; officers have a fourth death frame that our sheets leave out, and slots 46 and 47 are empty
+fn EnemySheetSprites(numDeathFrames: usize) -> Vec<Option<usize>> {
+    let pain2 = 44;
+    let dead = pain2 + 1 + (numDeathFrames - 3);
+    let mut slots: Vec<Option<usize>> = (0..41).map(Some).collect();     // standing, walking, first pain
+    slots.extend((41..44).map(Some));                                   // first three death frames
+    slots.push(Some(dead));
+    slots.extend([None, None]);
+    slots.push(Some(pain2));
+    slots.extend((dead+1..dead+4).map(Some));                           // shooting
+    slots
+}
+
+/*
+=========================================================
+    VSWAP struct definition
+=========================================================
+*/
+
+pub struct VSWAP {
+    pub walls: Vec<Bitmap>,
+
+    // Sprites that are missing or empty in the file come out fully transparent
+    pub sprites: Vec<Bitmap>
+}
+
+impl VSWAP {
+    pub fn Load(path: &str, palette: &Palette) -> Result<Self, LoadError> {
+        let data = fs::read(path).map_err(|error| LoadError::IO { path: path.to_string(), error })?;
+        let Invalid = |message: String| LoadError::IMAGE { path: path.to_string(), message };
+        let ReadU16 = |offset: usize| data.get(offset..offset+2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| Invalid(format!("file is truncated ; it is {} bytes long", data.len())));
+        let ReadU32 = |offset: usize| data.get(offset..offset+4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| Invalid(format!("file is truncated ; it is {} bytes long", data.len())));
+
+        let (numChunks, spriteStart, soundStart) = (ReadU16(0)?, ReadU16(2)?, ReadU16(4)?);
+        if spriteStart > soundStart || soundStart > numChunks {
+            return Err(Invalid(format!("header says {} chunks, sprites from {} and sounds from {}", numChunks, spriteStart, soundStart)));
+        }
+
+        let mut chunks: Vec<&[u8]> = Vec::new();
+        for i in 0..soundStart {
+            let (offset, length) = (ReadU32(6 + 4*i)?, ReadU16(6 + 4*numChunks + 2*i)?);
+            chunks.push(data.get(offset..offset+length)
+                .ok_or_else(|| Invalid(format!("chunk {} lies past the end of the file", i)))?);
+        }
+
+        let walls = chunks[..spriteStart].iter().enumerate()
+            .map(|(i, chunk)| DecodeWall(chunk, palette).map_err(|message| Invalid(format!("wall chunk {}: {}", i, message))))
+            .collect::<Result<Vec<Bitmap>, LoadError>>()?;
+        let sprites = chunks[spriteStart..].iter().enumerate()
+            .map(|(i, chunk)| DecodeSprite(chunk, palette).map_err(|message| Invalid(format!("sprite chunk {}: {}", spriteStart + i, message))))
+            .collect::<Result<Vec<Bitmap>, LoadError>>()?;
+
+        Ok(Self { walls, sprites })
+    }
+
+    // Fills every texture sheet from the file, in the same slots as the BMP sheets ; slots the file has nothing for stay transparent
+    pub fn TextureSheets(&self) -> HashMap<TextureType, Vec<Bitmap>> {
+        let Pick = |pages: &[Bitmap], index: Option<usize>| index.and_then(|i| pages.get(i)).cloned().unwrap_or_else(BlankTexture);
+        let weaponStart = self.sprites.len().saturating_sub(NUM_WEAPON_SPRITES);
+
+        let mut sheets: HashMap<TextureType, Vec<Bitmap>> = HashMap::new();
+        for spec in &TEXTURE_SHEET_SPECS {
+            let slots: Vec<Option<usize>> = match spec.textureType {
+                TextureType::WALL => (0..spec.numTextures as usize).map(Some).collect(),
+                TextureType::OBJECT => (0..spec.numTextures as usize).map(|i| Some(FIRST_OBJECT_SPRITE + i)).collect(),
+                TextureType::GUARD => EnemySheetSprites(3).iter().map(|slot| slot.map(|i| FIRST_GUARD_SPRITE + i)).collect(),
+                TextureType::SS => EnemySheetSprites(3).iter().map(|slot| slot.map(|i| FIRST_SS_SPRITE + i)).collect(),
+                TextureType::OFFICER => EnemySheetSprites(4).iter().map(|slot| slot.map(|i| FIRST_OFFICER_SPRITE + i)).collect(),
+                TextureType::WEAPON => (0..spec.numTextures as usize).map(|i| Some(weaponStart + i)).collect()
+            };
+            let pages = if spec.textureType == TextureType::WALL { &self.walls } else { &self.sprites };
+            sheets.insert(spec.textureType, slots.into_iter().map(|slot| Pick(pages, slot)).collect());
+        }
+
+        sheets
+    }
+}
+
+fn BlankTexture() -> Bitmap {
+    Bitmap {
+        width: TEXTURE_PITCH as usize,
+        height: TEXTURE_PITCH as usize,
+        pixels: vec![[0, 0, 0, 0]; (TEXTURE_PITCH*TEXTURE_PITCH) as usize]
+    }
+}
+
+fn PaletteColor(palette: &Palette, index: u8) -> [u8; 4] {
+    let [r, g, b] = palette[index as usize];
+    [r, g, b, 255]
+}
+
+fn DecodeWall(chunk: &[u8], palette: &Palette) -> Result<Bitmap, String> {
+    let pitch = TEXTURE_PITCH as usize;
+    if chunk.is_empty() {
+        return Ok(BlankTexture());
+    }
+    if chunk.len() != pitch*pitch {
+        return Err(format!("is {} bytes ; a wall is {}", chunk.len(), pitch*pitch));
+    }
+
+    let mut texture = BlankTexture();
+    for x in 0..pitch {
+        for y in 0..pitch {
+            texture.pixels[y*pitch + x] = PaletteColor(palette, chunk[x*pitch + y]);
+        }
+    }
+    Ok(texture)
+}
+
+fn DecodeSprite(chunk: &[u8], palette: &Palette) -> Result<Bitmap, String> {
+    let pitch = TEXTURE_PITCH as usize;
+    let mut texture = BlankTexture();
+    if chunk.is_empty() {
+        return Ok(texture);
+    }
+
+    let truncated = || "post data lies past the end of the sprite".to_string();
+    let ReadU16 = |offset: usize| chunk.get(offset..offset+2).map(|b| u16::from_le_bytes([b[0], b[1]])).ok_or_else(truncated);
+
+    let (leftColumn, rightColumn) = (ReadU16(0)? as usize, ReadU16(2)? as usize);
+    if leftColumn > rightColumn || rightColumn >= pitch {
+        return Err(format!("columns {}-{} ; expected a range within 0-{}", leftColumn, rightColumn, pitch-1));
+    }
+
+    for x in leftColumn..=rightColumn {
+        let mut post = ReadU16(4 + 2*(x - leftColumn))? as usize;
+        loop {
+            let endRow = ReadU16(post)? as usize / 2;
+            if endRow == 0 {
+                break;
+            }
+            // Stored as the offset of the post's first pixel minus its start row, so pixel y is at offset + y
+            let pixelsOffset = ReadU16(post + 2)? as i16 as isize;
+            let startRow = ReadU16(post + 4)? as usize / 2;
+            if startRow > endRow || endRow > pitch {
+                return Err(format!("post in column {} covers rows {}-{} ; expected a range within 0-{}", x, startRow, endRow, pitch));
+            }
+            for y in startRow..endRow {
+                let index = chunk.get((pixelsOffset + y as isize) as usize).ok_or_else(truncated)?;
+                texture.pixels[y*pitch + x] = PaletteColor(palette, *index);
+            }
+            post += 6;
+        }
+    }
+
+    Ok(texture)
+}
+
+/*
+=========================================================
+    Palette loading
+=========================================================
+*/
+
+// Either a JASC-PAL text file, as most palette editors save, or 768 raw bytes of R,G,B ; raw palettes with nothing
+// above 63 are taken to be 6-bit VGA DAC values, the way the game itself stores them, and scaled up
+pub fn LoadPalette(path: &str) -> Result<Palette, LoadError> {
+    let data = fs::read(path).map_err(|error| LoadError::IO { path: path.to_string(), error })?;
+    let Invalid = |message: String| LoadError::IMAGE { path: path.to_string(), message };
+
+    let components: Vec<u8> = if data.starts_with(b"JASC-PAL") {
+        let text = String::from_utf8_lossy(&data);
+        let numbers: Vec<&str> = text.split_whitespace().skip(3).collect();
+        numbers.iter()
+            .map(|n| n.parse::<u8>().map_err(|_| Invalid(format!("color value `{}` is not a number from 0 to 255", n))))
+            .collect::<Result<Vec<u8>, LoadError>>()?
+    } else if data.iter().all(|&c| c <= 63) {
+        data.iter().map(|&c| (c << 2) | (c >> 4)).collect()
+    } else {
+        data
+    };
+
+    if components.len() != 768 {
+        return Err(Invalid(format!("has {} color values ; a palette is 256 R,G,B colors", components.len())));
+    }
+
+    let mut palette: Palette = [[0, 0, 0]; 256];
+    for (color, rgb) in palette.iter_mut().zip(components.chunks(3)) {
+        *color = [rgb[0], rgb[1], rgb[2]];
+    }
+    Ok(palette)
+}
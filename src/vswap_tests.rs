@@ -0,0 +1,166 @@
+/*
+=========================================================
+    VSWAP loading tests
+
+    Synthetic VSWAP files and palettes built here, chunk by chunk, in the
+    original format ; none of the game's data is needed to run them
+=========================================================
+*/
+
+use std::fs;
+use crate::load_error::LoadError;
+use crate::multimedia::TextureType;
+use crate::utils::scratch::WriteScratchFile;
+use crate::vswap::{LoadPalette, Palette, VSWAP};
+
+const SCRATCH_DIR: &str = "target/vswap_tests";
+
+// Palette index i is (i, 255-i, 7), so a pixel's red value is its palette index
+fn TestPalette() -> Palette {
+    let path = WriteFile("palette.pal", &(0..=255u8).flat_map(|i| [i, 255 - i, 7]).collect::<Vec<u8>>());
+    LoadPalette(&path).unwrap()
+}
+
+fn WriteFile(name: &str, data: &[u8]) -> String {
+    WriteScratchFile(SCRATCH_DIR, name, data)
+}
+
+// Chunks are written in order right after the header ; no sounds
+fn WriteVSWAP(name: &str, walls: &[Vec<u8>], sprites: &[Vec<u8>]) -> String {
+    let chunks: Vec<&Vec<u8>> = walls.iter().chain(sprites.iter()).collect();
+    let numChunks = chunks.len();
+    let mut data: Vec<u8> = Vec::new();
+    for value in [numChunks, walls.len(), numChunks] {
+        data.extend((value as u16).to_le_bytes());
+    }
+
+    let mut offset = 6 + 6*numChunks;
+    for chunk in &chunks {
+        data.extend((if chunk.is_empty() { 0 } else { offset as u32 }).to_le_bytes());
+        offset += chunk.len();
+    }
+    for chunk in &chunks {
+        data.extend((chunk.len() as u16).to_le_bytes());
+    }
+    for chunk in &chunks {
+        data.extend(chunk.iter());
+    }
+    WriteFile(name, &data)
+}
+
+// Column major, like the original
+fn WallChunk(Texel: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+    (0..64).flat_map(|x| (0..64).map(move |y| (x, y))).map(|(x, y)| Texel(x, y)).collect()
+}
+
+// posts are (column, start row, end row, palette index) ; each column from the first to the last has at least one
+fn SpriteChunk(posts: &[(usize, usize, usize, u8)]) -> Vec<u8> {
+    let leftColumn = posts.iter().map(|post| post.0).min().unwrap();
+    let rightColumn = posts.iter().map(|post| post.0).max().unwrap();
+    let numColumns = rightColumn - leftColumn + 1;
+
+    // Header, then each column's post list, then every post's pixels
+    let headerSize = 4 + 2*numColumns;
+    let postListsSize: usize = (leftColumn..=rightColumn).map(|x| 6*posts.iter().filter(|post| post.0 == x).count() + 2).sum();
+    let mut header: Vec<u8> = Vec::new();
+    let mut postLists: Vec<u8> = Vec::new();
+    let mut pixels: Vec<u8> = Vec::new();
+    header.extend((leftColumn as u16).to_le_bytes());
+    header.extend((rightColumn as u16).to_le_bytes());
+    for x in leftColumn..=rightColumn {
+        header.extend(((headerSize + postLists.len()) as u16).to_le_bytes());
+        for &(_, startRow, endRow, color) in posts.iter().filter(|post| post.0 == x) {
+            let pixelsOffset = (headerSize + postListsSize + pixels.len()) as i32 - startRow as i32;
+            postLists.extend(((endRow*2) as u16).to_le_bytes());
+            postLists.extend((pixelsOffset as i16).to_le_bytes());
+            postLists.extend(((startRow*2) as u16).to_le_bytes());
+            pixels.extend(std::iter::repeat_n(color, endRow - startRow));
+        }
+        postLists.extend([0, 0]);
+    }
+
+    [header, postLists, pixels].concat()
+}
+
+#[test]
+fn walls_and_sprites_decode_through_the_palette() {
+    let palette = TestPalette();
+    let path = WriteVSWAP("decode.vswap", &[WallChunk(|x, y| if y == 0 { 4*x as u8 } else { 200 })], &[
+        SpriteChunk(&[(10, 5, 9, 40), (10, 20, 22, 44), (11, 0, 64, 48)]),
+        Vec::new()
+    ]);
+    let vswap = VSWAP::Load(&path, &palette).unwrap();
+
+    assert_eq!(vswap.walls[0].GetPixel(3, 0), [12, 243, 7, 255]);
+    assert_eq!(vswap.walls[0].GetPixel(3, 1), [200, 55, 7, 255]);
+
+    let sprite = &vswap.sprites[0];
+    assert_eq!(sprite.GetPixel(10, 5), [40, 215, 7, 255]);
+    assert_eq!(sprite.GetPixel(10, 8), [40, 215, 7, 255]);
+    assert_eq!(sprite.GetPixel(10, 9)[3], 0, "rows between posts are transparent");
+    assert_eq!(sprite.GetPixel(10, 21), [44, 211, 7, 255]);
+    assert_eq!(sprite.GetPixel(11, 63), [48, 207, 7, 255]);
+    assert_eq!(sprite.GetPixel(9, 5)[3], 0, "columns outside the sprite are transparent");
+    assert!(vswap.sprites[1].pixels.iter().all(|texel| texel[3] == 0), "an empty chunk is a blank sprite");
+}
+
+#[test]
+fn texture_sheets_follow_the_original_sprite_layout() {
+    // A file shaped like the registered version's, with a recognisable sprite at every index the test looks at
+    let numSprites = 300;
+    let marked: [(usize, u8); 8] = [(2, 4), (50, 8), (94, 12), (95, 16), (282, 28), (283, 36), (284, 20), (numSprites - 15, 24)];
+    let sprites: Vec<Vec<u8>> = (0..numSprites).map(|i| match marked.iter().find(|(index, _)| *index == i) {
+        Some(&(_, color)) => SpriteChunk(&[(32, 0, 64, color)]),
+        None => Vec::new()
+    }).collect();
+    let path = WriteVSWAP("layout.vswap", &[WallChunk(|_, _| 28), WallChunk(|_, _| 32)], &sprites);
+    let sheets = VSWAP::Load(&path, &TestPalette()).unwrap().TextureSheets();
+    let Red = |textureType: TextureType, ID: usize| {
+        let texel = sheets[&textureType][ID-1].GetPixel(32, 32);
+        if texel[3] == 0 { None } else { Some(texel[0]) }
+    };
+
+    assert_eq!((Red(TextureType::WALL, 1), Red(TextureType::WALL, 2), Red(TextureType::WALL, 3)), (Some(28), Some(32), None));
+    assert_eq!(Red(TextureType::OBJECT, 3), Some(4), "first static object, after the demo and death cam sprites");
+    assert_eq!(Red(TextureType::GUARD, 1), Some(8));
+    assert_eq!(Red(TextureType::GUARD, 48), Some(12), "second pain frame");
+    assert_eq!(Red(TextureType::GUARD, 45), Some(16), "dead body");
+    assert_eq!(Red(TextureType::OFFICER, 45), Some(20), "dead body, after the officer's fourth death frame");
+    assert_eq!(Red(TextureType::OFFICER, 48), Some(28), "second pain frame, before the officer's fourth death frame");
+    assert!(sheets[&TextureType::OFFICER].iter().all(|texture| texture.GetPixel(32, 32)[0] != 36), "the fourth death frame isn't used");
+    assert_eq!(Red(TextureType::WEAPON, 6), Some(24), "pistol, counted from the end of the file");
+    assert_eq!(sheets[&TextureType::GUARD].len(), 51);
+}
+
+#[test]
+fn palettes_load_from_jasc_and_raw_files() {
+    let jasc = format!("JASC-PAL\n0100\n256\n{}", (0..256).map(|i| format!("{} 0 {}\n", i, 255 - i)).collect::<String>());
+    let palette = LoadPalette(&WriteFile("jasc.pal", jasc.as_bytes())).unwrap();
+    assert_eq!((palette[0], palette[200]), ([0, 0, 255], [200, 0, 55]));
+
+    let raw: Vec<u8> = (0..=255u8).flat_map(|i| [i, i, 255]).collect();
+    assert_eq!(LoadPalette(&WriteFile("raw.pal", &raw)).unwrap()[10], [10, 10, 255]);
+
+    // The game's own 6-bit values, scaled so that 63 comes out as full brightness
+    let vga: Vec<u8> = (0..=255u8).flat_map(|i| [i % 64, 0, 63]).collect();
+    let palette = LoadPalette(&WriteFile("vga.pal", &vga)).unwrap();
+    assert_eq!((palette[1], palette[63]), ([4, 0, 255], [255, 0, 255]));
+
+    match LoadPalette(&WriteFile("short.pal", &[0; 700])) {
+        Err(e @ LoadError::IMAGE { .. }) => assert!(e.to_string().ends_with("has 700 color values ; a palette is 256 R,G,B colors"), "{}", e),
+        _ => panic!("expected an image error")
+    }
+}
+
+#[test]
+fn broken_vswap_reports_the_file() {
+    let path = WriteVSWAP("broken.vswap", &[WallChunk(|_, _| 0)], &[SpriteChunk(&[(70, 0, 4, 8)])]);
+    match VSWAP::Load(&path, &TestPalette()) {
+        Err(e @ LoadError::IMAGE { .. }) => assert_eq!(e.to_string(), format!("{}: sprite chunk 1: columns 70-70 ; expected a range within 0-63", path)),
+        _ => panic!("expected an image error")
+    }
+
+    let data = fs::read(&path).unwrap();
+    let truncated = WriteFile("truncated.vswap", &data[..data.len() - 10]);
+    assert!(matches!(VSWAP::Load(&truncated, &TestPalette()), Err(LoadError::IMAGE { .. })));
+}